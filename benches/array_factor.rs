@@ -0,0 +1,63 @@
+//! Time per direction of `calc_array_beam1` and `ArrayFactor` on a 127
+//! antenna station; run with `cargo bench --bench array_factor`.
+
+use std::{hint::black_box, time::Instant};
+
+use jm21cma::{
+    arbitrary_array::{calc_array_beam1, calc_phase_from_pointing, ArrayFactor},
+    coordinates::AltAz,
+    layout::{layout_cfg, Layout},
+};
+
+const NDIRS: usize = 100_000;
+
+fn main() {
+    let positions = "hex:2:6".parse::<Layout>().unwrap().positions().unwrap();
+    let (x, y, z) = layout_cfg(&positions, None).positions();
+    let w = vec![1.0; x.len()];
+    let lambda = 2.0;
+    let ptg = AltAz::from_zenith_az(0.3, 0.5);
+    let phi = calc_phase_from_pointing(&x, &y, &z, &ptg, lambda);
+    let dirs: Vec<_> = (0..NDIRS)
+        .map(|i| AltAz::new(1e-5 * i as f64, 1e-3 * i as f64).to_vec3d())
+        .collect();
+
+    let t = Instant::now();
+    let reference: Vec<_> = dirs
+        .iter()
+        .map(|d| calc_array_beam1(d, &x, &y, &z, &w, &phi, lambda))
+        .collect();
+    let t_reference = t.elapsed().as_secs_f64();
+    black_box(&reference);
+
+    let af = ArrayFactor::new(&x, &y, &z, &w, &phi, lambda);
+    let t = Instant::now();
+    let one_by_one: Vec<_> = dirs.iter().map(|d| af.eval(d)).collect();
+    let t_eval = t.elapsed().as_secs_f64();
+    black_box(&one_by_one);
+
+    let t = Instant::now();
+    let many = af.eval_many(&dirs);
+    let t_many = t.elapsed().as_secs_f64();
+    black_box(&many);
+
+    let err = reference
+        .iter()
+        .zip(&many)
+        .map(|(a, b)| (a - b).norm())
+        .fold(0.0, f64::max);
+    let per_dir = |t: f64| t / NDIRS as f64 * 1e9;
+    println!("{} antennas, {} directions", x.len(), NDIRS);
+    println!("calc_array_beam1       {:8.1} ns/dir", per_dir(t_reference));
+    println!(
+        "ArrayFactor::eval      {:8.1} ns/dir, x{:.2}",
+        per_dir(t_eval),
+        t_reference / t_eval
+    );
+    println!(
+        "ArrayFactor::eval_many {:8.1} ns/dir, x{:.2}",
+        per_dir(t_many),
+        t_reference / t_many
+    );
+    println!("max difference {:.1e}", err);
+}
@@ -18,4 +18,4 @@ fi
 outname=$1
 shift
 
-cargo run --bin calc_21cma_beam_patch --release -- -z $LAT -a 90 -c data/21cma_station.yaml -A $@ -w $FOV_W -p $FOV_PIX -o $outname
+cargo run --release -- array-beam-patch -z $LAT -a 0 -c data/21cma_station.yaml -A $@ -w $FOV_W -p $FOV_PIX -o $outname
@@ -19,5 +19,5 @@ shift
 for freq in $@
 do
     echo $freq
-    cargo run --bin calc_ant_beam --release -- -n $DATA_DIR/21cma_lp.nec -s $nside -f $freq -o ${prefix}_${freq}.fits
+    cargo run --release -- ant-beam -n $DATA_DIR/21cma_lp.nec -s $nside -f $freq -o ${prefix}_${freq}.fits
 done
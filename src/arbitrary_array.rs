@@ -2,10 +2,18 @@ use std::f64::consts::PI;
 
 use num::complex::Complex;
 
-use scorus::coordinates::{Vec3d};
+use scorus::coordinates::Vec3d;
 
-use crate::utils::angle2vec;
+use crate::{
+    cfg::{AntCfg, ArrayCfg, GroupCfg},
+    constants::LIGHT_SPEED,
+    coordinates::AltAz,
+    parallel::par_map,
+};
 
+/// array factor towards `pointing`, a unit vector in the station frame,
+/// with the steering phases `phi_list` subtracted; the reference for
+/// `ArrayFactor`, which is faster for more than a few directions
 #[allow(clippy::too_many_arguments)]
 pub fn calc_array_beam1(
     pointing: &Vec3d<f64>,
@@ -32,15 +40,161 @@ pub fn calc_array_beam1(
         .sum::<Complex<f64>>()
 }
 
+/// number of directions evaluated together by `ArrayFactor::eval_many`
+const BLOCK: usize = 16;
+
+/// array factor at one frequency and steering, with the antenna positions
+/// scaled by the wavenumber and the steering phases folded into complex
+/// weights once, to be evaluated towards many directions.
+/// Antennas with a zero weight are dropped.
+pub struct ArrayFactor {
+    kx: Vec<f64>,
+    ky: Vec<f64>,
+    kz: Vec<f64>,
+    w_re: Vec<f64>,
+    w_im: Vec<f64>,
+}
+
+impl ArrayFactor {
+    /// same arguments as `calc_array_beam1`
+    pub fn new(
+        x_list: &[f64],
+        y_list: &[f64],
+        z_list: &[f64],
+        w_list: &[f64],
+        phi_list: &[f64],
+        lambda: f64,
+    ) -> Self {
+        let k = 2.0 * PI / lambda;
+        let mut result = Self {
+            kx: Vec::new(),
+            ky: Vec::new(),
+            kz: Vec::new(),
+            w_re: Vec::new(),
+            w_im: Vec::new(),
+        };
+        for i in 0..x_list.len() {
+            if w_list[i] == 0.0 {
+                continue;
+            }
+            let w = Complex::from_polar(w_list[i], -phi_list[i]);
+            result.kx.push(k * x_list[i]);
+            result.ky.push(k * y_list[i]);
+            result.kz.push(k * z_list[i]);
+            result.w_re.push(w.re);
+            result.w_im.push(w.im);
+        }
+        result
+    }
+
+    pub fn eval(&self, dir: &Vec3d<f64>) -> Complex<f64> {
+        let mut out = [Complex::new(0.0, 0.0)];
+        self.eval_block::<1>(std::slice::from_ref(dir), &mut out);
+        out[0]
+    }
+
+    /// the array factor towards each of `dirs`, bit-identical to `eval`
+    pub fn eval_many(&self, dirs: &[Vec3d<f64>]) -> Vec<Complex<f64>> {
+        let nblocks = dirs.len().div_ceil(BLOCK);
+        par_map(0..nblocks, |b| {
+            let block = &dirs[b * BLOCK..((b + 1) * BLOCK).min(dirs.len())];
+            let mut out = [Complex::new(0.0, 0.0); BLOCK];
+            self.eval_block::<BLOCK>(block, &mut out);
+            out
+        })
+        .into_iter()
+        .flatten()
+        .take(dirs.len())
+        .collect()
+    }
+
+    /// the array factor towards the directions that are not None
+    pub fn eval_some(&self, dirs: &[Option<Vec3d<f64>>]) -> Vec<Option<Complex<f64>>> {
+        let valid: Vec<_> = dirs.iter().flatten().cloned().collect();
+        let mut af = self.eval_many(&valid).into_iter();
+        dirs.iter().map(|d| d.and_then(|_| af.next())).collect()
+    }
+
+    /// antennas in the outer loop, so that their data are loaded once per
+    /// block of directions; the loops over the block have no branch, so that
+    /// they can be vectorized
+    fn eval_block<const N: usize>(&self, dirs: &[Vec3d<f64>], out: &mut [Complex<f64>]) {
+        let n = dirs.len();
+        let mut dx = [0.0; N];
+        let mut dy = [0.0; N];
+        let mut dz = [0.0; N];
+        for (j, d) in dirs.iter().enumerate() {
+            (dx[j], dy[j], dz[j]) = (d.x, d.y, d.z);
+        }
+        let mut re = [0.0; N];
+        let mut im = [0.0; N];
+        let mut s = [0.0; N];
+        let mut c = [0.0; N];
+        for i in 0..self.kx.len() {
+            let (kx, ky, kz) = (self.kx[i], self.ky[i], self.kz[i]);
+            let (w_re, w_im) = (self.w_re[i], self.w_im[i]);
+            for j in 0..N {
+                (s[j], c[j]) = sin_cos(kx * dx[j] + ky * dy[j] + kz * dz[j]);
+            }
+            for j in 0..N {
+                re[j] += w_re * c[j] - w_im * s[j];
+                im[j] += w_re * s[j] + w_im * c[j];
+            }
+        }
+        for j in 0..n {
+            out[j] = Complex::new(re[j], im[j]);
+        }
+    }
+}
+
+/// sine and cosine without branches, accurate to a few ulp times the number
+/// of turns of x
+#[inline(always)]
+fn sin_cos(x: f64) -> (f64, f64) {
+    // x = q pi/2 + r with |r| <= pi/4, pi/2 split in two parts
+    const PIO2_HI: f64 = std::f64::consts::FRAC_PI_2;
+    const PIO2_LO: f64 = 6.123_233_995_736_766e-17;
+    let q = (x * std::f64::consts::FRAC_2_PI).round();
+    let r = (x - q * PIO2_HI) - q * PIO2_LO;
+    let r2 = r * r;
+    // taylor series up to r^15 and r^16
+    let sin_r = r
+        * (1.0
+            + r2 * (-1.0 / 6.0
+                + r2 * (1.0 / 120.0
+                    + r2 * (-1.0 / 5040.0
+                        + r2 * (1.0 / 362880.0
+                            + r2 * (-1.0 / 39916800.0
+                                + r2 * (1.0 / 6227020800.0 + r2 * (-1.0 / 1307674368000.0))))))));
+    let cos_r = 1.0
+        + r2 * (-0.5
+            + r2 * (1.0 / 24.0
+                + r2 * (-1.0 / 720.0
+                    + r2 * (1.0 / 40320.0
+                        + r2 * (-1.0 / 3628800.0
+                            + r2 * (1.0 / 479001600.0
+                                + r2 * (-1.0 / 87178291200.0 + r2 * (1.0 / 20922789888000.0))))))));
+    // rotate by the quadrant
+    let quadrant = (q as i64) & 3;
+    let swap = quadrant & 1 == 1;
+    let (s, c) = if swap { (cos_r, sin_r) } else { (sin_r, cos_r) };
+    let sign_s = if quadrant >= 2 { -1.0 } else { 1.0 };
+    let sign_c = if quadrant == 1 || quadrant == 2 {
+        -1.0
+    } else {
+        1.0
+    };
+    (sign_s * s, sign_c * c)
+}
+
 pub fn calc_phase_from_pointing(
     x_list: &[f64],
     y_list: &[f64],
     z_list: &[f64],
-    az_from_east: f64,
-    zenith: f64,
+    pointing: &AltAz,
     lambda: f64,
 ) -> Vec<f64> {
-    let dir = angle2vec(az_from_east, zenith);
+    let dir = pointing.to_vec3d();
     x_list
         .iter()
         .zip(y_list.iter().zip(z_list.iter()))
@@ -50,3 +204,306 @@ pub fn calc_phase_from_pointing(
         })
         .collect()
 }
+
+/// delays (in s) that the beamformer has to insert to steer the array
+/// towards pointing
+pub fn calc_delay_from_pointing(
+    x_list: &[f64],
+    y_list: &[f64],
+    z_list: &[f64],
+    pointing: &AltAz,
+) -> Vec<f64> {
+    let dir = pointing.to_vec3d();
+    x_list
+        .iter()
+        .zip(y_list.iter().zip(z_list.iter()))
+        .map(|(&x, (&y, &z))| {
+            let dl = dir[0] * x + dir[1] * y + dir[2] * z;
+            dl / LIGHT_SPEED
+        })
+        .collect()
+}
+
+/// round delays to what a delay line with a resolution of `step` (in s) and
+/// `nbits` control bits can realize.
+/// Delays are counted from the smallest one, which only adds a common phase.
+pub fn quantize_delays(delays: &[f64], step: f64, nbits: Option<u32>) -> Vec<f64> {
+    let d0 = delays.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max_code = nbits.map_or(f64::INFINITY, |n| 2_f64.powi(n as i32) - 1.0);
+    delays
+        .iter()
+        .map(|&d| d0 + ((d - d0) / step).round().min(max_code) * step)
+        .collect()
+}
+
+/// phases at freq (in Hz) corresponding to the steering delays, to be used
+/// in place of `calc_phase_from_pointing`
+pub fn calc_phase_from_delay(delays: &[f64], freq: f64) -> Vec<f64> {
+    delays.iter().map(|&d| 2.0 * PI * freq * d).collect()
+}
+
+/// beam of a hierarchical (e.g. station then pod) beamformer.
+/// The delay lines of the top level steer their inputs towards `steering`,
+/// those of the groups at depth `i + 1` towards `group_steering[i]`, the
+/// last direction being reused by all deeper levels.
+pub fn calc_nested_array_beam(
+    pointing: &Vec3d<f64>,
+    cfg: &ArrayCfg,
+    steering: &Vec3d<f64>,
+    group_steering: &[Vec3d<f64>],
+    lambda: f64,
+) -> Complex<f64> {
+    calc_level_beam(
+        pointing,
+        &cfg.ants,
+        &cfg.groups,
+        steering,
+        group_steering,
+        lambda,
+    )
+}
+
+fn calc_level_beam(
+    pointing: &Vec3d<f64>,
+    ants: &[AntCfg],
+    groups: &[GroupCfg],
+    ptg0: &Vec3d<f64>,
+    group_steering: &[Vec3d<f64>],
+    lambda: f64,
+) -> Complex<f64> {
+    let (inner_ptg, inner_steering) = match group_steering {
+        [] => (ptg0, group_steering),
+        [next, rest @ ..] => (next, rest),
+    };
+    let freq = LIGHT_SPEED / lambda;
+    let geometric = |(x, y, z): (f64, f64, f64)| {
+        let dl =
+            (pointing[0] - ptg0[0]) * x + (pointing[1] - ptg0[1]) * y + (pointing[2] - ptg0[2]) * z;
+        Complex::from_polar(1.0, dl / lambda * 2.0 * PI)
+    };
+
+    let ant_sum = ants
+        .iter()
+        .map(|a| a.gain.gain(freq) * geometric(a.pos))
+        .sum::<Complex<f64>>();
+    let group_sum = groups
+        .iter()
+        .map(|g| {
+            g.gain.gain(freq)
+                * geometric(g.center)
+                * calc_level_beam(
+                    pointing,
+                    &g.ants,
+                    &g.groups,
+                    inner_ptg,
+                    inner_steering,
+                    lambda,
+                )
+        })
+        .sum::<Complex<f64>>();
+    ant_sum + group_sum
+}
+
+/// path lengths (in m) compensated by the delay lines of a hierarchical
+/// beamformer steered as in `calc_nested_array_beam`, in the order of
+/// `ArrayCfg::all_ants`; with no `group_steering`, the path lengths towards
+/// `pointing`
+pub fn calc_nested_path_lengths(
+    cfg: &ArrayCfg,
+    pointing: &AltAz,
+    group_steering: &[AltAz],
+) -> Vec<f64> {
+    let dirs: Vec<_> = std::iter::once(pointing)
+        .chain(group_steering)
+        .map(|d| d.to_vec3d())
+        .collect();
+    cfg.level_offsets()
+        .iter()
+        .map(|offsets| {
+            offsets
+                .iter()
+                .enumerate()
+                .map(|(level, &(x, y, z))| {
+                    let dir = &dirs[level.min(dirs.len() - 1)];
+                    dir[0] * x + dir[1] * y + dir[2] * z
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// steering phases at freq (in Hz) towards pointing, the groups of each
+/// level being steered by `group_steering` as in `calc_nested_array_beam`,
+/// plus the instrumental phases of the antennas of `cfg`; `delay_lines` holds
+/// the resolution (in s) and the number of control bits of the delay lines,
+/// the steering being ideal if None.
+/// The delay of each antenna, summed over the levels, is quantized.
+pub fn calc_steering_phases(
+    cfg: &ArrayCfg,
+    pointing: &AltAz,
+    group_steering: &[AltAz],
+    freq: f64,
+    delay_lines: Option<(f64, Option<u32>)>,
+) -> Vec<f64> {
+    let path_lengths = calc_nested_path_lengths(cfg, pointing, group_steering);
+    let mut phases = if let Some((step, nbits)) = delay_lines {
+        let delays: Vec<_> = path_lengths.iter().map(|dl| dl / LIGHT_SPEED).collect();
+        calc_phase_from_delay(&quantize_delays(&delays, step, nbits), freq)
+    } else {
+        let lambda = LIGHT_SPEED / freq;
+        path_lengths
+            .iter()
+            .map(|dl| dl / lambda * 2.0 * PI)
+            .collect()
+    };
+    cfg.apply_phase_offsets(&mut phases, freq);
+    phases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{layout_cfg, Layout};
+
+    #[test]
+    fn sin_cos_matches_std_over_large_arguments() {
+        // dense near zero and across the quadrant boundaries, then up to 1e3 rad
+        let xs = (-20000..=20000)
+            .map(|i| i as f64 * 1e-3)
+            .chain((0..=40000).map(|i| -1e3 + i as f64 * 0.05 + 1e-7 * i as f64))
+            .chain((-640..=640).map(|q| q as f64 * std::f64::consts::FRAC_PI_4));
+        for x in xs {
+            let (s, c) = sin_cos(x);
+            let tol = 4.0 * f64::EPSILON * (1.0 + x.abs());
+            assert!(
+                (s - x.sin()).abs() < tol,
+                "sin({}) = {} != {}",
+                x,
+                s,
+                x.sin()
+            );
+            assert!(
+                (c - x.cos()).abs() < tol,
+                "cos({}) = {} != {}",
+                x,
+                c,
+                x.cos()
+            );
+        }
+    }
+
+    #[test]
+    fn array_factor_matches_calc_array_beam1() {
+        // an irregular 40 m wide array with a dropped antenna and a tilt
+        let n = 50;
+        let x: Vec<_> = (0..n)
+            .map(|i| 40.0 * ((i * 37 % 101) as f64 / 101.0 - 0.5))
+            .collect();
+        let y: Vec<_> = (0..n)
+            .map(|i| 40.0 * ((i * 59 % 103) as f64 / 103.0 - 0.5))
+            .collect();
+        let z: Vec<_> = x.iter().map(|x| 0.01 * x).collect();
+        let w: Vec<_> = (0..n)
+            .map(|i| if i == 7 { 0.0 } else { 1.0 + 0.01 * i as f64 })
+            .collect();
+        let lambda = 1.5;
+        let ptg = AltAz::from_zenith_az(0.5, 1.0);
+        let phi = calc_phase_from_pointing(&x, &y, &z, &ptg, lambda);
+        let af = ArrayFactor::new(&x, &y, &z, &w, &phi, lambda);
+
+        let dirs: Vec<_> = (0..37)
+            .map(|i| AltAz::new(0.04 * i as f64, 0.3 * i as f64).to_vec3d())
+            .chain(std::iter::once(ptg.to_vec3d()))
+            .collect();
+        let many = af.eval_many(&dirs);
+        for (d, &m) in dirs.iter().zip(&many) {
+            let expected = calc_array_beam1(d, &x, &y, &z, &w, &phi, lambda);
+            assert!((af.eval(d) - expected).norm() < 1e-10 * n as f64);
+            assert_eq!(af.eval(d), m);
+        }
+        // all in phase towards the pointing
+        let total: f64 = w.iter().sum();
+        assert!((many[dirs.len() - 1].norm() - total).abs() < 1e-9);
+    }
+
+    /// hexagonal pods of 7 antennas on a hexagonal grid of 7, a pod holding a
+    /// nested pair, plus one antenna at the top level, with gains at all levels
+    fn nested_cfg() -> ArrayCfg {
+        let grid = |spec: &str| spec.parse::<Layout>().unwrap().positions().unwrap();
+        let mut cfg = layout_cfg(&grid("hex:6:1"), Some(&grid("hex:1.5:1")));
+        let mut top = AntCfg::new((-9.0, 4.0, 0.1));
+        top.gain.delay_ns = 0.3;
+        cfg.ants.push(top);
+        cfg.groups[1].gain.delay_ns = 0.5;
+        cfg.groups[2].gain.weight = 0.5;
+        cfg.groups[3].ants[2].gain.phase_deg = 20.0;
+        cfg.groups[4].gain.flagged = true;
+        let mut pair = layout_cfg(&[(0.0, 2.0)], Some(&[(-0.4, 0.0), (0.4, 0.0)])).groups;
+        pair[0].gain.phase_deg = -10.0;
+        cfg.groups[5].groups = pair;
+        cfg
+    }
+
+    #[test]
+    fn nested_steering_matches_the_flattened_array() {
+        let cfg = nested_cfg();
+        let (x, y, z) = cfg.positions();
+        let w = cfg.weights();
+        let freq = 150e6;
+        let lambda = LIGHT_SPEED / freq;
+        let station = AltAz::from_zenith_az(0.4, 0.3);
+        let pods = AltAz::from_zenith_az(0.2, 2.0);
+        let pairs = AltAz::from_zenith_az(0.1, -1.0);
+        for group_steering in [vec![], vec![pods], vec![pods, pairs]] {
+            let phi = calc_steering_phases(&cfg, &station, &group_steering, freq, None);
+            let af = ArrayFactor::new(&x, &y, &z, &w, &phi, lambda);
+            let inner: Vec<_> = group_steering.iter().map(|d| d.to_vec3d()).collect();
+            for i in 0..20 {
+                let dir = AltAz::new(0.08 * i as f64, 0.9 * i as f64).to_vec3d();
+                let nested =
+                    calc_nested_array_beam(&dir, &cfg, &station.to_vec3d(), &inner, lambda);
+                assert!((af.eval(&dir) - nested).norm() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn group_steering_defaults_to_the_pointing() {
+        let cfg = nested_cfg();
+        let (x, y, z) = cfg.positions();
+        let ptg = AltAz::from_zenith_az(0.4, 0.3);
+        let lambda = 2.0;
+        let flat = calc_phase_from_pointing(&x, &y, &z, &ptg, lambda);
+        for group_steering in [vec![], vec![ptg], vec![ptg, ptg]] {
+            let nested = calc_nested_path_lengths(&cfg, &ptg, &group_steering);
+            for (dl, phi) in nested.iter().zip(&flat) {
+                assert!((dl / lambda * 2.0 * PI - phi).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn quantize_delays_rounds_to_the_step_and_clamps_the_code() {
+        let step = 1e-9;
+        let delays = [5e-9, 5.4e-9, 5.6e-9, 7.49e-9, 7.51e-9, 30e-9];
+        let q = quantize_delays(&delays, step, None);
+        let expected = [5e-9, 5e-9, 6e-9, 7e-9, 8e-9, 30e-9];
+        for (a, b) in q.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-21);
+        }
+
+        // 3 bits: codes up to 7 steps above the smallest delay
+        let q = quantize_delays(&delays, step, Some(3));
+        let expected = [5e-9, 5e-9, 6e-9, 7e-9, 8e-9, 12e-9];
+        for (a, b) in q.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-21);
+        }
+
+        // negative delays are counted from the smallest one too
+        let q = quantize_delays(&[-2.2e-9, 0.0, 1.3e-9], step, Some(1));
+        let expected = [-2.2e-9, -1.2e-9, -1.2e-9];
+        for (a, b) in q.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-21);
+        }
+    }
+}
@@ -0,0 +1,592 @@
+//! Beam models behind a common interface.
+//!
+//! Directions are given in the station frame and frequencies in Hz. The Jones
+//! matrix holds E_theta and E_phi of the feed along x followed by those of the
+//! feed along y, in the order of `dipole::lp_ant_jones`.
+
+use std::{
+    collections::BTreeMap,
+    f64::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+use num::complex::Complex;
+
+use crate::{
+    arbitrary_array::{calc_steering_phases, ArrayFactor},
+    cfg::ArrayCfg,
+    constants::LIGHT_SPEED,
+    coordinates::AltAz,
+    dipole::{lp_ant_jones, x_dipole_jones},
+    parallel::par_map,
+    single_ant_model::{SingleAnt, SingleAntPol},
+};
+
+/// `Sync` so that the beams can be evaluated in parallel
+pub trait Beam: Sync {
+    fn jones(&self, dir: &AltAz, freq: f64) -> [Complex<f64>; 4];
+
+    /// E_theta and E_phi of the feed along x
+    fn efield(&self, dir: &AltAz, freq: f64) -> (Complex<f64>, Complex<f64>) {
+        let j = self.jones(dir, freq);
+        (j[0], j[1])
+    }
+
+    /// power pattern of the feed along x
+    fn power(&self, dir: &AltAz, freq: f64) -> f64 {
+        let (e_theta, e_phi) = self.efield(dir, freq);
+        e_theta.norm_sqr() + e_phi.norm_sqr()
+    }
+
+    /// jones matrices towards `dirs`, None where the direction is None;
+    /// beams set up per frequency (e.g. `ArrayBeam`) do so once
+    fn jones_many(&self, dirs: &[Option<AltAz>], freq: f64) -> Vec<Option<[Complex<f64>; 4]>> {
+        par_map(0..dirs.len(), |i| dirs[i].map(|d| self.jones(&d, freq)))
+    }
+
+    /// power patterns of the feed along x towards `dirs`, zero where the
+    /// direction is None
+    fn power_many(&self, dirs: &[Option<AltAz>], freq: f64) -> Vec<f64> {
+        par_map(0..dirs.len(), |i| {
+            dirs[i].map_or(0.0, |d| self.power(&d, freq))
+        })
+    }
+}
+
+impl<B: Beam + ?Sized> Beam for Box<B> {
+    fn jones(&self, dir: &AltAz, freq: f64) -> [Complex<f64>; 4] {
+        (**self).jones(dir, freq)
+    }
+
+    fn power(&self, dir: &AltAz, freq: f64) -> f64 {
+        (**self).power(dir, freq)
+    }
+
+    fn jones_many(&self, dirs: &[Option<AltAz>], freq: f64) -> Vec<Option<[Complex<f64>; 4]>> {
+        (**self).jones_many(dirs, freq)
+    }
+
+    fn power_many(&self, dirs: &[Option<AltAz>], freq: f64) -> Vec<f64> {
+        (**self).power_many(dirs, freq)
+    }
+}
+
+impl<B: Beam + ?Sized> Beam for &B {
+    fn jones(&self, dir: &AltAz, freq: f64) -> [Complex<f64>; 4] {
+        (**self).jones(dir, freq)
+    }
+
+    fn power(&self, dir: &AltAz, freq: f64) -> f64 {
+        (**self).power(dir, freq)
+    }
+
+    fn jones_many(&self, dirs: &[Option<AltAz>], freq: f64) -> Vec<Option<[Complex<f64>; 4]>> {
+        (**self).jones_many(dirs, freq)
+    }
+
+    fn power_many(&self, dirs: &[Option<AltAz>], freq: f64) -> Vec<f64> {
+        (**self).power_many(dirs, freq)
+    }
+}
+
+/// the pattern is that of `freq_MHz`, whatever the frequency asked for;
+/// the polarization is that of an idealized log-periodic antenna
+impl Beam for SingleAnt {
+    fn jones(&self, dir: &AltAz, _freq: f64) -> [Complex<f64>; 4] {
+        let sph = dir.to_sph_coord();
+        lp_ant_jones(sph.az, sph.pol, self.power_pattern(sph.az, sph.pol))
+    }
+
+    fn power(&self, dir: &AltAz, _freq: f64) -> f64 {
+        let sph = dir.to_sph_coord();
+        self.power_pattern(sph.az, sph.pol)
+    }
+}
+
+/// the pattern is that of `freq_MHz`, whatever the frequency asked for
+impl Beam for SingleAntPol {
+    fn jones(&self, dir: &AltAz, _freq: f64) -> [Complex<f64>; 4] {
+        let sph = dir.to_sph_coord();
+        SingleAntPol::jones(self, sph.az, sph.pol)
+    }
+}
+
+/// crossed half-wave or other thin dipoles along x and y
+#[derive(Clone, Copy, Debug)]
+pub struct CrossedDipole {
+    /// full length, in m
+    pub length: f64,
+}
+
+impl Beam for CrossedDipole {
+    fn jones(&self, dir: &AltAz, freq: f64) -> [Complex<f64>; 4] {
+        let sph = dir.to_sph_coord();
+        x_dipole_jones(sph.az, sph.pol, LIGHT_SPEED / freq, self.length)
+    }
+}
+
+/// element beams by the names used in `ArrayCfg::elements`
+pub type ElementBeams = BTreeMap<String, Box<dyn Beam>>;
+
+/// jones matrix of `beam` rotated by `orientation` (in rad, from north
+/// towards east) about the vertical; the rows stay those of the rotated feeds
+pub fn rotated_jones<B: Beam + ?Sized>(
+    beam: &B,
+    dir: &AltAz,
+    freq: f64,
+    orientation: f64,
+) -> [Complex<f64>; 4] {
+    if orientation == 0.0 {
+        beam.jones(dir, freq)
+    } else {
+        beam.jones(&AltAz::new(dir.alt, dir.az - orientation), freq)
+    }
+}
+
+/// the antennas of an array at one frequency and steering, each with its own
+/// element beam and orientation, to sum the element jones matrices when they
+/// cannot be factored out of the array factor.
+/// Antennas with a zero weight are dropped.
+pub struct ElementSum {
+    pos: Vec<(f64, f64, f64)>,
+    weights: Vec<Complex<f64>>,
+    orientations: Vec<f64>,
+    /// index of the element of each antenna in the beams of `ElementSum::beams`
+    element_idx: Vec<usize>,
+    /// names of the elements used, the default one being index 0
+    names: Vec<String>,
+    freq: f64,
+}
+
+impl ElementSum {
+    /// `weights` and `phases` in the order of `ArrayCfg::all_ants`, as for
+    /// `calc_array_beam1`
+    pub fn new(cfg: &ArrayCfg, weights: &[f64], phases: &[f64], freq: f64) -> Self {
+        let mut result = Self {
+            pos: Vec::new(),
+            weights: Vec::new(),
+            orientations: Vec::new(),
+            element_idx: Vec::new(),
+            names: Vec::new(),
+            freq,
+        };
+        for ((a, &w), &phi) in cfg.all_ants().iter().zip(weights).zip(phases) {
+            if w == 0.0 {
+                continue;
+            }
+            result.pos.push(a.pos);
+            result.weights.push(Complex::from_polar(w, -phi));
+            result.orientations.push(a.orientation_deg.to_radians());
+            let idx = match &a.element {
+                None => 0,
+                Some(e) => match result.names.iter().position(|n| n == e) {
+                    Some(k) => k + 1,
+                    None => {
+                        result.names.push(e.clone());
+                        result.names.len()
+                    }
+                },
+            };
+            result.element_idx.push(idx);
+        }
+        result
+    }
+
+    /// the beams of the elements used, `default` for the antennas without an
+    /// element or whose element has no beam in `elements`
+    pub fn beams<'a>(
+        &self,
+        default: &'a dyn Beam,
+        elements: &'a ElementBeams,
+    ) -> Vec<&'a dyn Beam> {
+        std::iter::once(default)
+            .chain(
+                self.names
+                    .iter()
+                    .map(|n| elements.get(n).map_or(default, |b| &**b)),
+            )
+            .collect()
+    }
+
+    /// `beams` as given by `ElementSum::beams`
+    pub fn jones(&self, dir: &AltAz, beams: &[&dyn Beam]) -> [Complex<f64>; 4] {
+        let v = dir.to_vec3d();
+        let k = 2.0 * PI * self.freq / LIGHT_SPEED;
+        let mut result = [Complex::new(0.0, 0.0); 4];
+        for i in 0..self.pos.len() {
+            let (x, y, z) = self.pos[i];
+            let w = self.weights[i] * Complex::from_polar(1.0, k * (v.x * x + v.y * y + v.z * z));
+            let j = rotated_jones(
+                beams[self.element_idx[i]],
+                dir,
+                self.freq,
+                self.orientations[i],
+            );
+            result.iter_mut().zip(j).for_each(|(r, j)| *r += w * j);
+        }
+        result
+    }
+}
+
+/// the array factor, and the element sum if the elements differ, at one
+/// frequency and pointing
+struct Steered {
+    freq: f64,
+    pointing: AltAz,
+    factor: ArrayFactor,
+    sum: Option<ElementSum>,
+}
+
+/// elements combined by the beamformer of an array; the element beam is
+/// factored out of the sum over the antennas unless the antennas of the cfg
+/// differ in orientation or element beam.
+/// The steering is set up once per frequency and pointing and kept until
+/// either changes.
+pub struct ArrayBeam<B> {
+    pub element: B,
+    /// beams of the named elements of the cfg, see `ElementSum::beams`
+    pub elements: ElementBeams,
+    pub pointing: AltAz,
+    cfg: ArrayCfg,
+    weights: Vec<f64>,
+    group_steering: Vec<AltAz>,
+    delay_lines: Option<(f64, Option<u32>)>,
+    ant_x: Vec<f64>,
+    ant_y: Vec<f64>,
+    ant_z: Vec<f64>,
+    identical_elements: bool,
+    steered: Mutex<Option<Arc<Steered>>>,
+}
+
+impl<B: Beam> ArrayBeam<B> {
+    /// weights of the cfg and ideal steering towards pointing
+    pub fn new(element: B, cfg: ArrayCfg, pointing: AltAz) -> Self {
+        let (ant_x, ant_y, ant_z) = cfg.positions();
+        let weights = cfg.weights();
+        let identical_elements = cfg.has_identical_elements();
+        Self {
+            element,
+            elements: ElementBeams::new(),
+            pointing,
+            cfg,
+            weights,
+            group_steering: Vec::new(),
+            delay_lines: None,
+            ant_x,
+            ant_y,
+            ant_z,
+            identical_elements,
+            steered: Mutex::new(None),
+        }
+    }
+
+    /// beam of the antennas whose element is `name`
+    pub fn with_element(mut self, name: &str, beam: impl Beam + 'static) -> Self {
+        self.elements.insert(name.to_string(), Box::new(beam));
+        self
+    }
+
+    /// beams of the antennas by element name
+    pub fn with_elements(mut self, elements: ElementBeams) -> Self {
+        self.elements.extend(elements);
+        self
+    }
+
+    /// amplitude weights of the antennas, in the order of `ArrayCfg::all_ants`
+    pub fn with_weights(mut self, weights: Vec<f64>) -> Self {
+        assert_eq!(weights.len(), self.ant_x.len());
+        self.weights = weights;
+        *self.steered.get_mut().unwrap() = None;
+        self
+    }
+
+    /// resolution (in s) and number of control bits of the delay lines,
+    /// instead of the ideal phase steering
+    pub fn with_delay_lines(mut self, step: f64, nbits: Option<u32>) -> Self {
+        self.delay_lines = Some((step, nbits));
+        *self.steered.get_mut().unwrap() = None;
+        self
+    }
+
+    /// directions towards which the delay lines of the groups of the cfg are
+    /// steered, level by level, as in `calc_nested_array_beam`; the groups
+    /// follow the pointing by default
+    pub fn with_group_steering(mut self, group_steering: Vec<AltAz>) -> Self {
+        self.group_steering = group_steering;
+        *self.steered.get_mut().unwrap() = None;
+        self
+    }
+
+    pub fn cfg(&self) -> &ArrayCfg {
+        &self.cfg
+    }
+
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    pub fn phases(&self, freq: f64) -> Vec<f64> {
+        calc_steering_phases(
+            &self.cfg,
+            &self.pointing,
+            &self.group_steering,
+            freq,
+            self.delay_lines,
+        )
+    }
+
+    /// array factor at freq, to be evaluated towards many directions
+    pub fn factor(&self, freq: f64) -> ArrayFactor {
+        self.factor_with(&self.phases(freq), freq)
+    }
+
+    fn factor_with(&self, phases: &[f64], freq: f64) -> ArrayFactor {
+        ArrayFactor::new(
+            &self.ant_x,
+            &self.ant_y,
+            &self.ant_z,
+            &self.weights,
+            phases,
+            LIGHT_SPEED / freq,
+        )
+    }
+
+    pub fn array_factor(&self, dir: &AltAz, freq: f64) -> Complex<f64> {
+        self.steered(freq).factor.eval(&dir.to_vec3d())
+    }
+
+    /// the steering at freq towards the current pointing, set up again only
+    /// if either changed since the last call
+    fn steered(&self, freq: f64) -> Arc<Steered> {
+        let mut cache = self.steered.lock().unwrap();
+        if let Some(s) = cache.as_ref() {
+            if s.freq == freq && s.pointing == self.pointing {
+                return s.clone();
+            }
+        }
+        let phases = self.phases(freq);
+        let s = Arc::new(Steered {
+            freq,
+            pointing: self.pointing,
+            factor: self.factor_with(&phases, freq),
+            sum: (!self.identical_elements)
+                .then(|| ElementSum::new(&self.cfg, &self.weights, &phases, freq)),
+        });
+        *cache = Some(s.clone());
+        s
+    }
+}
+
+impl<B: Beam> Beam for ArrayBeam<B> {
+    fn jones(&self, dir: &AltAz, freq: f64) -> [Complex<f64>; 4] {
+        let s = self.steered(freq);
+        match &s.sum {
+            None => {
+                let af = s.factor.eval(&dir.to_vec3d());
+                self.element.jones(dir, freq).map(|j| af * j)
+            }
+            Some(sum) => sum.jones(dir, &sum.beams(&self.element, &self.elements)),
+        }
+    }
+
+    fn power(&self, dir: &AltAz, freq: f64) -> f64 {
+        let s = self.steered(freq);
+        match &s.sum {
+            None => s.factor.eval(&dir.to_vec3d()).norm_sqr() * self.element.power(dir, freq),
+            Some(sum) => {
+                let j = sum.jones(dir, &sum.beams(&self.element, &self.elements));
+                j[0].norm_sqr() + j[1].norm_sqr()
+            }
+        }
+    }
+
+    fn jones_many(&self, dirs: &[Option<AltAz>], freq: f64) -> Vec<Option<[Complex<f64>; 4]>> {
+        let s = self.steered(freq);
+        match &s.sum {
+            None => {
+                let vecs: Vec<_> = dirs.iter().map(|d| d.map(|d| d.to_vec3d())).collect();
+                let af = s.factor.eval_some(&vecs);
+                par_map(0..dirs.len(), |i| {
+                    dirs[i]
+                        .zip(af[i])
+                        .map(|(d, af)| self.element.jones(&d, freq).map(|j| af * j))
+                })
+            }
+            Some(sum) => {
+                let beams = sum.beams(&self.element, &self.elements);
+                par_map(0..dirs.len(), |i| dirs[i].map(|d| sum.jones(&d, &beams)))
+            }
+        }
+    }
+
+    fn power_many(&self, dirs: &[Option<AltAz>], freq: f64) -> Vec<f64> {
+        let s = self.steered(freq);
+        if s.sum.is_some() {
+            return self
+                .jones_many(dirs, freq)
+                .into_iter()
+                .map(|j| j.map_or(0.0, |j| j[0].norm_sqr() + j[1].norm_sqr()))
+                .collect();
+        }
+        let vecs: Vec<_> = dirs.iter().map(|d| d.map(|d| d.to_vec3d())).collect();
+        let af = s.factor.eval_some(&vecs);
+        par_map(0..dirs.len(), |i| match (dirs[i], af[i]) {
+            (Some(d), Some(af)) => af.norm_sqr() * self.element.power(&d, freq),
+            _ => 0.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dipole::x_dipole_E,
+        layout::{layout_cfg, Layout},
+    };
+    use std::f64::consts::FRAC_PI_2;
+
+    const FREQ: f64 = 100e6;
+
+    fn station() -> ArrayCfg {
+        layout_cfg(
+            &"hex:2:2".parse::<Layout>().unwrap().positions().unwrap(),
+            None,
+        )
+    }
+
+    fn dirs() -> Vec<Option<AltAz>> {
+        (0..60)
+            .map(|i| {
+                let (alt, az) = (i as f64 * 0.03, i as f64 * 0.7);
+                (alt > 0.0).then(|| AltAz::new(alt, az))
+            })
+            .collect()
+    }
+
+    /// crossed dipoles whose y feed is the x feed turned by 90 deg from east
+    /// towards south, whereas the y row of `CrossedDipole` is the x row
+    /// rotated in polarization, which is the same only at the zenith
+    #[derive(Clone, Copy)]
+    struct TurnedDipoles {
+        length: f64,
+    }
+
+    impl Beam for TurnedDipoles {
+        fn jones(&self, dir: &AltAz, freq: f64) -> [Complex<f64>; 4] {
+            let sph = dir.to_sph_coord();
+            let lambda = LIGHT_SPEED / freq;
+            let (xt, xp) = x_dipole_E(sph.az, sph.pol, lambda, self.length);
+            let (yt, yp) = x_dipole_E(sph.az + FRAC_PI_2, sph.pol, lambda, self.length);
+            [xt, xp, yt, yp].map(Complex::from)
+        }
+    }
+
+    fn close(a: &[Complex<f64>], b: &[Complex<f64>]) -> bool {
+        a.iter()
+            .zip(b)
+            .all(|(a, b)| (a - b).norm() < 1e-9 * (1.0 + b.norm()))
+    }
+
+    #[test]
+    fn many_directions_match_one_by_one() {
+        let dipole = CrossedDipole { length: 1.5 };
+        let mut cfg = station();
+        cfg.ants[3].orientation_deg = 30.0;
+        for cfg in [station(), cfg] {
+            let beam = ArrayBeam::new(dipole, cfg, AltAz::new(1.2, 0.5));
+            let many = beam.jones_many(&dirs(), FREQ);
+            let power = beam.power_many(&dirs(), FREQ);
+            for ((d, j), p) in dirs().iter().zip(&many).zip(&power) {
+                match (d, j) {
+                    (Some(d), Some(j)) => {
+                        assert!(close(j, &beam.jones(d, FREQ)));
+                        assert!((p - beam.power(d, FREQ)).abs() < 1e-9 * (1.0 + p));
+                    }
+                    (None, None) => assert_eq!(*p, 0.0),
+                    _ => panic!("None where the direction is None"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn steering_follows_the_pointing() {
+        let dipole = CrossedDipole { length: 1.5 };
+        let mut beam = ArrayBeam::new(dipole, station(), AltAz::new(1.2, 0.5));
+        let dir = AltAz::new(0.9, 2.0);
+        beam.jones(&dir, FREQ);
+        for pointing in [
+            AltAz::new(1.0, 1.0),
+            AltAz::new(1.0, 1.0),
+            AltAz::new(0.8, 3.0),
+        ] {
+            beam.pointing = pointing;
+            let fresh = ArrayBeam::new(dipole, station(), pointing);
+            for freq in [FREQ, 1.5 * FREQ] {
+                assert_eq!(beam.jones(&dir, freq), fresh.jones(&dir, freq));
+            }
+        }
+    }
+
+    #[test]
+    fn element_sum_matches_the_factored_beam() {
+        let dipole = CrossedDipole { length: 1.5 };
+        let factored = ArrayBeam::new(dipole, station(), AltAz::new(1.2, 0.5));
+        // the same dipole under a name, so that it is not factored out
+        let mut cfg = station();
+        cfg.ants[0].element = Some("same".to_string());
+        let summed = ArrayBeam::new(dipole, cfg, AltAz::new(1.2, 0.5)).with_element("same", dipole);
+        for d in dirs().iter().flatten() {
+            assert!(close(&summed.jones(d, FREQ), &factored.jones(d, FREQ)));
+        }
+
+        // an antenna rotated by 90 deg swaps the roles of its feeds: the x
+        // feed lies along the unrotated y feed, the y feed along -x
+        let dipoles = TurnedDipoles { length: 1.5 };
+        let cfg = layout_cfg(&[(0.0, 0.0)], None);
+        let unrotated = ArrayBeam::new(dipoles, cfg.clone(), AltAz::new(1.2, 0.5));
+        let mut cfg = cfg;
+        cfg.ants[0].orientation_deg = 90.0;
+        let rotated = ArrayBeam::new(dipoles, cfg, AltAz::new(1.2, 0.5));
+        for d in dirs().iter().flatten() {
+            let (j, u) = (rotated.jones(d, FREQ), unrotated.jones(d, FREQ));
+            assert!(close(&j[..2], &u[2..]));
+            assert!(close(&j[2..], &[-u[0], -u[1]]));
+        }
+    }
+
+    /// `f` on a pool of a single thread, i.e., one sample after the other
+    fn on_one_thread<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+        #[cfg(feature = "parallel")]
+        return rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(f);
+        #[cfg(not(feature = "parallel"))]
+        f()
+    }
+
+    #[test]
+    fn parallel_evaluation_is_bit_identical_to_serial() {
+        let dipole = CrossedDipole { length: 1.5 };
+        let mut cfg = station();
+        cfg.ants[3].orientation_deg = 30.0;
+        // enough directions for several blocks of the array factor
+        let dirs: Vec<_> = (0..3000)
+            .map(|i| {
+                let (alt, az) = (i as f64 * 7e-4 - 0.1, i as f64 * 0.37);
+                (alt > 0.0).then(|| AltAz::new(alt, az))
+            })
+            .collect();
+        for cfg in [station(), cfg] {
+            let beam = ArrayBeam::new(dipole, cfg, AltAz::new(1.2, 0.5));
+            let (power, jones) =
+                on_one_thread(|| (beam.power_many(&dirs, FREQ), beam.jones_many(&dirs, FREQ)));
+            let bits = |p: &[f64]| p.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(&power), bits(&beam.power_many(&dirs, FREQ)));
+            assert_eq!(jones, beam.jones_many(&dirs, FREQ));
+        }
+    }
+}
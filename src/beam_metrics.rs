@@ -0,0 +1,315 @@
+//! Figures of merit of a sampled power beam.
+//!
+//! The beam is given as samples: unit vectors in some frame, the power and the
+//! solid angle of each sample, so that HEALPix maps and patches are handled
+//! alike. Position angles are measured at the peak from the direction towards
+//! the +z pole of the frame, towards the direction of increasing longitude
+//! (`atan2(y, x)`).
+
+use std::f64::consts::PI;
+
+use scorus::coordinates::Vec3d;
+
+use crate::error::{Error, Result};
+
+pub struct BeamMetrics {
+    pub peak_dir: Vec3d<f64>,
+    pub peak: f64,
+    /// full widths at half maximum along the principal axes, in rad
+    pub fwhm_major: f64,
+    pub fwhm_minor: f64,
+    /// position angle of the major axis, in rad
+    pub fwhm_pa: f64,
+    /// radius of the main lobe (the first minimum of the azimuthally averaged
+    /// profile around the peak), in rad
+    pub first_null: f64,
+    /// highest first sidelobe relative to the peak
+    pub sidelobe_level: f64,
+    /// integral of the normalized beam over the samples, in sr
+    pub solid_angle: f64,
+    /// integral of the normalized beam over the main lobe, in sr
+    pub main_beam_solid_angle: f64,
+}
+
+impl BeamMetrics {
+    pub fn main_beam_efficiency(&self) -> f64 {
+        self.main_beam_solid_angle / self.solid_angle
+    }
+
+    /// only meaningful if the samples cover the full sky
+    pub fn directivity(&self) -> f64 {
+        4.0 * PI / self.solid_angle
+    }
+
+    pub fn effective_area(&self, lambda: f64) -> f64 {
+        lambda * lambda / self.solid_angle
+    }
+}
+
+/// local east and north unit vectors of the tangent plane at `p`
+fn tangent_basis(p: &Vec3d<f64>) -> (Vec3d<f64>, Vec3d<f64>) {
+    let pole = if p.x.hypot(p.y) > 1e-12 {
+        Vec3d {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        }
+    } else {
+        Vec3d {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    };
+    let n = (pole + *p * (-pole.dot(*p))).normalized();
+    let e = Vec3d {
+        x: n.y * p.z - n.z * p.y,
+        y: n.z * p.x - n.x * p.z,
+        z: n.x * p.y - n.y * p.x,
+    };
+    (e, n)
+}
+
+/// fails unless there are samples, all finite, and a positive peak
+pub fn calc_beam_metrics(
+    dirs: &[Vec3d<f64>],
+    power: &[f64],
+    pix_area: &[f64],
+) -> Result<BeamMetrics> {
+    if dirs.len() != power.len() || dirs.len() != pix_area.len() {
+        return Err(Error::Invalid(format!(
+            "{} directions for {} beam values and {} solid angles",
+            dirs.len(),
+            power.len(),
+            pix_area.len()
+        )));
+    }
+    if power.iter().chain(pix_area).any(|x| !x.is_finite()) {
+        return Err(Error::Invalid(
+            "non-finite beam values or solid angles".to_string(),
+        ));
+    }
+    let ipeak = (0..power.len())
+        .max_by(|&a, &b| power[a].total_cmp(&power[b]))
+        .ok_or_else(|| Error::Invalid("no beam samples".to_string()))?;
+    let peak = power[ipeak];
+    if peak <= 0.0 {
+        return Err(Error::Invalid("the beam is nowhere positive".to_string()));
+    }
+    let p = dirs[ipeak];
+    let dist: Vec<f64> = dirs
+        .iter()
+        .map(|v| v.dot(p).clamp(-1.0, 1.0).acos())
+        .collect();
+
+    // azimuthally averaged profile, with bins of about the sample spacing
+    let mut areas = pix_area.to_vec();
+    areas.sort_by(|a, b| a.total_cmp(b));
+    let bin = areas[areas.len() / 2].sqrt();
+    let nbins = (PI / bin).ceil() as usize + 1;
+    let mut sum = vec![0.0; nbins];
+    let mut cnt = vec![0.0; nbins];
+    for (&d, (&x, &a)) in dist.iter().zip(power.iter().zip(pix_area.iter())) {
+        let k = (d / bin) as usize;
+        sum[k] += x * a;
+        cnt[k] += a;
+    }
+    let profile: Vec<Option<f64>> = sum
+        .iter()
+        .zip(cnt.iter())
+        .map(|(&s, &c)| if c > 0.0 { Some(s / c) } else { None })
+        .collect();
+    let filled: Vec<(usize, f64)> = profile
+        .iter()
+        .enumerate()
+        .filter_map(|(k, x)| x.map(|x| (k, x)))
+        .collect();
+    // extrema over a few bins, so that the noise of the profile is ignored
+    let n = filled.len();
+    let window = |i: usize| &filled[i.saturating_sub(2)..(i + 3).min(n)];
+    let is_max = |i: usize| window(i).iter().all(|x| x.1 <= filled[i].1);
+    let is_min = |i: usize| window(i).iter().all(|x| x.1 >= filled[i].1);
+    // first minimum after the maximum following `start`
+    let next_min = |start: usize| {
+        let imax = (start..n).find(|&i| is_max(i)).unwrap_or(n - 1);
+        (imax + 1..n).find(|&i| is_min(i)).unwrap_or(n - 1)
+    };
+    let i_null = next_min(0);
+    let i_null2 = next_min(i_null);
+    let first_null = (filled[i_null].0 as f64 + 0.5) * bin;
+    let second_null = (filled[i_null2].0 as f64 + 0.5) * bin;
+
+    let sidelobe = dist
+        .iter()
+        .zip(power.iter())
+        .filter(|(&d, _)| d >= first_null && (d < second_null || i_null2 == i_null))
+        .map(|(_, &x)| x)
+        .fold(0.0, f64::max);
+
+    let solid_angle = power
+        .iter()
+        .zip(pix_area.iter())
+        .map(|(&x, &a)| x * a)
+        .sum::<f64>()
+        / peak;
+    let main_beam_solid_angle = power
+        .iter()
+        .zip(pix_area.iter().zip(dist.iter()))
+        .filter(|(_, (_, &d))| d < first_null)
+        .map(|(&x, (&a, _))| x * a)
+        .sum::<f64>()
+        / peak;
+
+    // second moments of the half power region in the azimuthal equidistant
+    // projection around the peak; a filled ellipse with semi-axes a and b has
+    // moments a^2/4 and b^2/4, each sample adding that of a square pixel
+    let (e, n) = tangent_basis(&p);
+    let (mut cee, mut cnn, mut cen, mut w) = (0.0, 0.0, 0.0, 0.0);
+    for ((v, &x), (&a, &d)) in dirs
+        .iter()
+        .zip(power.iter())
+        .zip(pix_area.iter().zip(dist.iter()))
+    {
+        if d < first_null && x >= peak / 2.0 {
+            let (ve, vn) = (v.dot(e), v.dot(n));
+            let r = ve.hypot(vn);
+            let (pe, pn) = if r > 0.0 {
+                (ve / r * d, vn / r * d)
+            } else {
+                (0.0, 0.0)
+            };
+            cee += a * (pe * pe + a / 12.0);
+            cnn += a * (pn * pn + a / 12.0);
+            cen += a * pe * pn;
+            w += a;
+        }
+    }
+    let (cee, cnn, cen) = (cee / w, cnn / w, cen / w);
+    let tr = (cee + cnn) / 2.0;
+    let det = ((cee - cnn) / 2.0).hypot(cen);
+    let fwhm_major = 4.0 * (tr + det).sqrt();
+    let fwhm_minor = 4.0 * (tr - det).max(0.0).sqrt();
+    // the major axis makes an angle of atan2(2 cen, cnn - cee) / 2 with n
+    let fwhm_pa = ((2.0 * cen).atan2(cnn - cee) / 2.0).rem_euclid(PI);
+
+    Ok(BeamMetrics {
+        peak_dir: p,
+        peak,
+        fwhm_major,
+        fwhm_minor,
+        fwhm_pa,
+        first_null,
+        sidelobe_level: sidelobe / peak,
+        solid_angle,
+        main_beam_solid_angle,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bessel function J1 from its integral representation
+    fn bessel_j1(x: f64) -> f64 {
+        let n = 64;
+        (0..n)
+            .map(|i| {
+                let t = (i as f64 + 0.5) * PI / n as f64;
+                (t - x * t.sin()).cos()
+            })
+            .sum::<f64>()
+            / n as f64
+    }
+
+    /// power of a uniformly illuminated circular aperture, peak 1
+    fn airy(x: f64) -> f64 {
+        if x == 0.0 {
+            1.0
+        } else {
+            (2.0 * bessel_j1(x) / x).powi(2)
+        }
+    }
+
+    /// samples on a grid around the zenith, `step` apart in the azimuthal
+    /// equidistant projection
+    fn grid(step: f64, half_width: f64) -> (Vec<Vec3d<f64>>, Vec<f64>) {
+        let n = (half_width / step) as i64;
+        let mut dirs = Vec::new();
+        let mut areas = Vec::new();
+        for i in -n..=n {
+            for j in -n..=n {
+                let (u, v) = (i as f64 * step, j as f64 * step);
+                let r = u.hypot(v);
+                let s = if r > 0.0 { r.sin() / r } else { 1.0 };
+                dirs.push(Vec3d {
+                    x: u * s,
+                    y: v * s,
+                    z: r.cos(),
+                });
+                areas.push(step * step * s);
+            }
+        }
+        (dirs, areas)
+    }
+
+    // lambda / D
+    const LD: f64 = 0.05;
+
+    #[test]
+    fn airy_beam_widths_and_first_sidelobe() {
+        let (dirs, areas) = grid(0.0015, 0.15);
+        let power: Vec<_> = dirs
+            .iter()
+            .map(|v| airy(PI / LD * v.z.clamp(-1.0, 1.0).acos()))
+            .collect();
+        let m = calc_beam_metrics(&dirs, &power, &areas).unwrap();
+        assert_eq!(m.peak, 1.0);
+        assert!((m.fwhm_major / (1.029 * LD) - 1.0).abs() < 0.02);
+        assert!((m.fwhm_minor / (1.029 * LD) - 1.0).abs() < 0.02);
+        assert!((m.first_null / (1.2197 * LD) - 1.0).abs() < 0.02);
+        // -17.57 dB
+        assert!((10.0 * m.sidelobe_level.log10() + 17.57).abs() < 0.2);
+    }
+
+    #[test]
+    fn elliptical_beam_axes_and_position_angle() {
+        let (dirs, areas) = grid(0.0015, 0.15);
+        let zenith = Vec3d {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        let (e, n) = tangent_basis(&zenith);
+        for pa in [0.0, 0.4, 2.0_f64] {
+            // the major axis 1.5 times the minor one, at `pa` from n towards e
+            let power: Vec<_> = dirs
+                .iter()
+                .map(|v| {
+                    let (pe, pn) = (v.dot(e), v.dot(n));
+                    let major = pn * pa.cos() + pe * pa.sin();
+                    let minor = -pn * pa.sin() + pe * pa.cos();
+                    airy(PI / LD * (major / 1.5).hypot(minor))
+                })
+                .collect();
+            let m = calc_beam_metrics(&dirs, &power, &areas).unwrap();
+            assert!((m.fwhm_major / (1.5 * 1.029 * LD) - 1.0).abs() < 0.02);
+            assert!((m.fwhm_minor / (1.029 * LD) - 1.0).abs() < 0.02);
+            // an axis, defined modulo pi
+            let d = (m.fwhm_pa - pa).rem_euclid(PI);
+            assert!(d.min(PI - d) < 0.02);
+        }
+    }
+
+    #[test]
+    fn degenerate_samples_are_errors() {
+        let (dirs, areas) = grid(0.01, 0.05);
+        let power: Vec<_> = dirs.iter().map(|v| v.z).collect();
+        assert!(calc_beam_metrics(&dirs, &power, &areas).is_ok());
+        assert!(calc_beam_metrics(&[], &[], &[]).is_err());
+        assert!(calc_beam_metrics(&dirs, &power[1..], &areas).is_err());
+        let mut nan = power.clone();
+        nan[3] = f64::NAN;
+        assert!(calc_beam_metrics(&dirs, &nan, &areas).is_err());
+        assert!(calc_beam_metrics(&dirs, &vec![0.0; dirs.len()], &areas).is_err());
+    }
+}
@@ -1,11 +1,719 @@
-use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, f64::consts::PI, fs::File, path::Path, str::FromStr};
+
+use num::complex::Complex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result, WithPath},
+    geodesy::geodetic_to_ecef,
+    validation::hard_errors,
+};
+
+/// a configuration (or any other yaml input) read from `path`
+pub fn load_yaml<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let file = File::open(path).with_path(path)?;
+    serde_yaml::from_reader(file).with_path(path)
+}
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ArrayCfg {
+    /// frame and length unit of `pos` of the antennas and `center` of the
+    /// groups; `from_yaml` converts everything to m in the enu frame
+    #[serde(default)]
+    pub frame: PosFrame,
+
+    #[serde(default)]
+    pub units: LengthUnit,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ants: Vec<AntCfg>,
+
+    /// sub-arrays (e.g. pods) combined by their own analog beamformer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<GroupCfg>,
+
+    /// element beams by name, for antennas that differ from the default one
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub elements: BTreeMap<String, ElementCfg>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site: Option<SiteCfg>,
+}
+
+/// coordinates of the antenna positions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PosFrame {
+    /// east, north and up relative to the site, the frame of all computations
+    #[default]
+    Enu,
+    /// earth-centred, earth-fixed x, y and z (ITRF)
+    Ecef,
+    /// WGS84 longitude and latitude in deg and height above the ellipsoid
+    Geodetic,
+}
+
+impl FromStr for PosFrame {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "enu" => Ok(PosFrame::Enu),
+            "ecef" | "itrf" => Ok(PosFrame::Ecef),
+            "geodetic" => Ok(PosFrame::Geodetic),
+            _ => Err(format!("unknown frame {}, use enu, ecef or geodetic", s)),
+        }
+    }
+}
+
+impl PosFrame {
+    /// names of the three coordinates, in csv headers
+    pub fn columns(&self) -> [&'static str; 3] {
+        match self {
+            PosFrame::Enu => ["east", "north", "up"],
+            PosFrame::Ecef => ["x", "y", "z"],
+            PosFrame::Geodetic => ["lon_deg", "lat_deg", "height"],
+        }
+    }
+
+    /// ECEF position in m of `pos`, with lengths in units of `scale` m
+    fn to_ecef(self, pos: (f64, f64, f64), scale: f64) -> (f64, f64, f64) {
+        match self {
+            PosFrame::Enu => unreachable!(),
+            PosFrame::Ecef => (pos.0 * scale, pos.1 * scale, pos.2 * scale),
+            PosFrame::Geodetic => {
+                geodetic_to_ecef(pos.0.to_radians(), pos.1.to_radians(), pos.2 * scale)
+            }
+        }
+    }
+}
+
+/// unit of the lengths of the antenna positions, angles are always in deg
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LengthUnit {
+    #[default]
+    M,
+    Cm,
+    Mm,
+}
+
+impl FromStr for LengthUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "m" => Ok(LengthUnit::M),
+            "cm" => Ok(LengthUnit::Cm),
+            "mm" => Ok(LengthUnit::Mm),
+            _ => Err(format!("unknown unit {}, use m, cm or mm", s)),
+        }
+    }
+}
+
+impl LengthUnit {
+    /// the unit in m
+    pub fn scale(&self) -> f64 {
+        match self {
+            LengthUnit::M => 1.0,
+            LengthUnit::Cm => 1e-2,
+            LengthUnit::Mm => 1e-3,
+        }
+    }
+}
+
+/// geodetic location of the station reference point
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SiteCfg {
+    pub lon_deg: f64,
+    pub lat_deg: f64,
+    #[serde(default)]
+    pub height_m: f64,
+}
+
+/// stations correlated as an interferometer
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InterferometerCfg {
+    pub site: SiteCfg,
+    pub stations: Vec<StationCfg>,
+}
+
+impl InterferometerCfg {
+    pub fn from_yaml(path: &str) -> Result<Self> {
+        load_yaml(path)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StationCfg {
+    pub name: String,
+
+    /// reference point of the station in the east-north-up frame of the site,
+    /// the antenna positions of `array` are relative to it
+    pub pos: (f64, f64, f64),
+
+    /// array cfg file of the station, relative to the interferometer cfg
+    pub array: String,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct AntCfg {
     pub pos: (f64, f64, f64),
+
+    #[serde(flatten)]
+    pub gain: GainCfg,
+
+    /// rotation of the element about the vertical in deg, from north towards
+    /// east; at 0 the x feed is along the east
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub orientation_deg: f64,
+
+    /// key of the element beam in `ArrayCfg::elements`, the default element
+    /// (e.g. the beam given on the command line) if None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub element: Option<String>,
+}
+
+/// element beam referred to by the antennas
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ElementCfg {
+    /// healpix beams, one file per frequency or a multi-HDU file, as given to
+    /// `--ant_beam`; relative paths are relative to the cfg file
+    Beam { files: Vec<String> },
+    /// crossed thin dipoles along the x and y axes of the element
+    Dipole { length_m: f64 },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GroupCfg {
+    /// position of the group centre relative to the parent level
+    pub center: (f64, f64, f64),
+
+    #[serde(flatten)]
+    pub gain: GainCfg,
+
+    /// antenna positions are relative to `center`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ants: Vec<AntCfg>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<GroupCfg>,
+}
+
+/// state of the analog chain behind an antenna or a group
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GainCfg {
+    /// amplitude weight of the signal
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    pub weight: f64,
+
+    /// residual phase offset in deg
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub phase_deg: f64,
+
+    /// residual cable delay in ns
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub delay_ns: f64,
+
+    /// dead antennas are excluded from the sum
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub flagged: bool,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn is_default_weight(x: &f64) -> bool {
+    *x == default_weight()
+}
+
+fn is_zero(x: &f64) -> bool {
+    *x == 0.0
+}
+
+fn is_false(x: &bool) -> bool {
+    !x
+}
+
+impl Default for GainCfg {
+    fn default() -> Self {
+        Self {
+            weight: default_weight(),
+            phase_deg: 0.0,
+            delay_ns: 0.0,
+            flagged: false,
+        }
+    }
+}
+
+impl GainCfg {
+    pub fn effective_weight(&self) -> f64 {
+        if self.flagged {
+            0.0
+        } else {
+            self.weight
+        }
+    }
+
+    /// phase (in rad) added to the signal by the analog chain at freq (in Hz)
+    pub fn phase_offset(&self, freq: f64) -> f64 {
+        self.phase_deg.to_radians() + 2.0 * PI * freq * self.delay_ns * 1e-9
+    }
+
+    pub fn gain(&self, freq: f64) -> Complex<f64> {
+        Complex::from_polar(self.effective_weight(), -self.phase_offset(freq))
+    }
+
+    /// the gain of a signal passing through `self` and then `outer`
+    pub fn chain(&self, outer: &GainCfg) -> GainCfg {
+        GainCfg {
+            weight: self.weight * outer.weight,
+            phase_deg: self.phase_deg + outer.phase_deg,
+            delay_ns: self.delay_ns + outer.delay_ns,
+            flagged: self.flagged || outer.flagged,
+        }
+    }
+}
+
+impl AntCfg {
+    pub fn new(pos: (f64, f64, f64)) -> Self {
+        Self {
+            pos,
+            gain: GainCfg::default(),
+            orientation_deg: 0.0,
+            element: None,
+        }
+    }
+}
+
+impl GroupCfg {
+    fn collect_ants(
+        &self,
+        parent_pos: (f64, f64, f64),
+        parent_gain: &GainCfg,
+        result: &mut Vec<AntCfg>,
+    ) {
+        let center = (
+            parent_pos.0 + self.center.0,
+            parent_pos.1 + self.center.1,
+            parent_pos.2 + self.center.2,
+        );
+        let gain = self.gain.chain(parent_gain);
+        result.extend(self.ants.iter().map(|a| AntCfg {
+            pos: (a.pos.0 + center.0, a.pos.1 + center.1, a.pos.2 + center.2),
+            gain: a.gain.chain(&gain),
+            ..a.clone()
+        }));
+        for g in &self.groups {
+            g.collect_ants(center, &gain, result);
+        }
+    }
+
+    fn collect_offsets(
+        &self,
+        parent_offsets: &[(f64, f64, f64)],
+        result: &mut Vec<Vec<(f64, f64, f64)>>,
+    ) {
+        let mut offsets = parent_offsets.to_vec();
+        offsets.push(self.center);
+        result.extend(self.ants.iter().map(|a| {
+            let mut o = offsets.clone();
+            o.push(a.pos);
+            o
+        }));
+        for g in &self.groups {
+            g.collect_offsets(&offsets, result);
+        }
+    }
+}
+
+impl ArrayCfg {
+    /// positions converted to m in the enu frame, fails on the hard errors
+    /// of `validation::hard_errors`
+    pub fn from_yaml(path: &str) -> Result<Self> {
+        let cfg = Self::from_yaml_unchecked(path)?;
+        let errors = hard_errors(&cfg);
+        if errors.is_empty() {
+            Ok(cfg)
+        } else {
+            Err(Error::Invalid(format!("{}: {}", path, errors.join("; "))))
+        }
+    }
+
+    /// positions converted to m in the enu frame and element beam files
+    /// relative to the directory of the cfg, without any other check
+    pub fn from_yaml_unchecked(path: &str) -> Result<Self> {
+        let mut cfg: Self = load_yaml(path)?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        for e in cfg.elements.values_mut() {
+            if let ElementCfg::Beam { files } = e {
+                files
+                    .iter_mut()
+                    .for_each(|f| *f = dir.join(&f).to_string_lossy().into_owned());
+            }
+        }
+        cfg.to_enu()
+            .map_err(|e| Error::Invalid(format!("{}: {}", path, e)))
+    }
+
+    /// whether all antennas have the default element beam and orientation,
+    /// so that the element beam factors out of the sum over the antennas
+    pub fn has_identical_elements(&self) -> bool {
+        self.all_ants()
+            .iter()
+            .all(|a| a.orientation_deg == 0.0 && a.element.is_none())
+    }
+
+    /// antennas of a csv table with a header line, holding the columns
+    /// `PosFrame::columns` of `frame` and optionally `weight`, `phase_deg`,
+    /// `delay_ns`, `flagged`, `orientation_deg` and `element`; other columns
+    /// are ignored. The positions are
+    /// kept in `frame` and `units`, with `site` as reference point.
+    pub fn from_csv(
+        path: &str,
+        frame: PosFrame,
+        units: LengthUnit,
+        site: Option<SiteCfg>,
+    ) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_path(path)?;
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty() && !l.trim_start().starts_with('#'));
+        let header: Vec<_> = match lines.next() {
+            Some((_, l)) => l.split(',').map(|c| c.trim().to_lowercase()).collect(),
+            None => return Err(Error::Invalid(format!("{}: empty table", path))),
+        };
+        let col = |name: &str| header.iter().position(|c| c == name);
+        let pos_cols = frame
+            .columns()
+            .iter()
+            .map(|&c| {
+                col(c).ok_or_else(|| Error::MissingColumn {
+                    path: path.to_string(),
+                    column: c.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let gain_cols = ["weight", "phase_deg", "delay_ns", "flagged"].map(col);
+        let (orientation_col, element_col) = (col("orientation_deg"), col("element"));
+
+        let mut ants = Vec::new();
+        for (iline, line) in lines {
+            let fields: Vec<_> = line.split(',').map(|f| f.trim()).collect();
+            let invalid =
+                |what: &str| Error::Invalid(format!("{}:{}: invalid {}", path, iline + 1, what));
+            let num = |icol: usize| -> Result<f64> {
+                fields
+                    .get(icol)
+                    .and_then(|f| f.parse::<f64>().ok())
+                    .ok_or_else(|| invalid(&header[icol]))
+            };
+            let mut ant = AntCfg::new((num(pos_cols[0])?, num(pos_cols[1])?, num(pos_cols[2])?));
+            if let Some(i) = gain_cols[0] {
+                ant.gain.weight = num(i)?;
+            }
+            if let Some(i) = gain_cols[1] {
+                ant.gain.phase_deg = num(i)?;
+            }
+            if let Some(i) = gain_cols[2] {
+                ant.gain.delay_ns = num(i)?;
+            }
+            if let Some(i) = gain_cols[3] {
+                ant.gain.flagged = match fields.get(i).map(|f| f.to_lowercase()).as_deref() {
+                    Some("true") | Some("1") => true,
+                    Some("false") | Some("0") | Some("") => false,
+                    _ => return Err(invalid("flagged")),
+                };
+            }
+            if let Some(i) = orientation_col {
+                ant.orientation_deg = num(i)?;
+            }
+            if let Some(i) = element_col {
+                ant.element = fields
+                    .get(i)
+                    .filter(|f| !f.is_empty())
+                    .map(|f| f.to_string());
+            }
+            ants.push(ant);
+        }
+        Ok(Self {
+            frame,
+            units,
+            ants,
+            groups: Vec::new(),
+            elements: BTreeMap::new(),
+            site,
+        })
+    }
+
+    /// the same antennas with positions in m in the enu frame of the site;
+    /// ecef and geodetic positions need the site and no groups
+    pub fn to_enu(mut self) -> Result<Self> {
+        let scale = self.units.scale();
+        match self.frame {
+            PosFrame::Enu => {
+                fn scale_group(g: &mut GroupCfg, s: f64) {
+                    g.center = (g.center.0 * s, g.center.1 * s, g.center.2 * s);
+                    g.ants
+                        .iter_mut()
+                        .for_each(|a| a.pos = (a.pos.0 * s, a.pos.1 * s, a.pos.2 * s));
+                    g.groups.iter_mut().for_each(|g| scale_group(g, s));
+                }
+                if self.units != LengthUnit::M {
+                    self.ants
+                        .iter_mut()
+                        .for_each(|a| a.pos = (a.pos.0 * scale, a.pos.1 * scale, a.pos.2 * scale));
+                    self.groups.iter_mut().for_each(|g| scale_group(g, scale));
+                }
+            }
+            frame => {
+                if !self.groups.is_empty() {
+                    return Err(Error::Invalid(
+                        "groups need positions in the enu frame".to_string(),
+                    ));
+                }
+                let site = self.site.clone().ok_or_else(|| {
+                    Error::Invalid(
+                        "ecef and geodetic positions need the site as reference point".to_string(),
+                    )
+                })?;
+                for a in &mut self.ants {
+                    a.pos = site.ecef_to_enu(frame.to_ecef(a.pos, scale));
+                }
+            }
+        }
+        self.frame = PosFrame::Enu;
+        self.units = LengthUnit::M;
+        Ok(self)
+    }
+
+    /// geodetic location of the mean ECEF position of the antennas of an ecef
+    /// or geodetic cfg, to serve as site
+    pub fn centroid_site(&self) -> Option<SiteCfg> {
+        if self.frame == PosFrame::Enu || self.ants.is_empty() {
+            return None;
+        }
+        let n = self.ants.len() as f64;
+        let scale = self.units.scale();
+        let c = self.ants.iter().fold((0.0, 0.0, 0.0), |s, a| {
+            let p = self.frame.to_ecef(a.pos, scale);
+            (s.0 + p.0 / n, s.1 + p.1 / n, s.2 + p.2 / n)
+        });
+        Some(SiteCfg::from_ecef(c))
+    }
+
+    /// all antennas with absolute positions and the gains of all levels
+    /// applied, i.e., the equivalent single-level array
+    pub fn all_ants(&self) -> Vec<AntCfg> {
+        let mut result = self.ants.clone();
+        for g in &self.groups {
+            g.collect_ants((0.0, 0.0, 0.0), &GainCfg::default(), &mut result);
+        }
+        result
+    }
+
+    /// position of each antenna split by level: the centres of the groups
+    /// holding it from the outermost one, then its position in the innermost
+    /// group; in the order of `all_ants`
+    pub fn level_offsets(&self) -> Vec<Vec<(f64, f64, f64)>> {
+        let mut result: Vec<_> = self.ants.iter().map(|a| vec![a.pos]).collect();
+        for g in &self.groups {
+            g.collect_offsets(&[], &mut result);
+        }
+        result
+    }
+
+    pub fn positions(&self) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+        let (x, (y, z)): (Vec<f64>, (Vec<f64>, Vec<f64>)) = self
+            .all_ants()
+            .iter()
+            .map(|a| {
+                let (x, y, z) = a.pos;
+                (x, (y, z))
+            })
+            .unzip();
+        (x, y, z)
+    }
+
+    pub fn weights(&self) -> Vec<f64> {
+        self.all_ants()
+            .iter()
+            .map(|a| a.gain.effective_weight())
+            .collect()
+    }
+
+    /// add the instrumental phases to the steering phases, so that they can be
+    /// fed directly to `calc_array_beam1`
+    pub fn apply_phase_offsets(&self, phases: &mut [f64], freq: f64) {
+        phases
+            .iter_mut()
+            .zip(self.all_ants().iter())
+            .for_each(|(p, a)| *p += a.gain.phase_offset(freq));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `text` written to a csv file in the temporary directory
+    fn csv(name: &str, text: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("jm21cma_{}_{}.csv", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn site() -> SiteCfg {
+        SiteCfg {
+            lon_deg: 86.72,
+            lat_deg: 42.552673743,
+            height_m: 2650.0,
+        }
+    }
+
+    #[test]
+    fn csv_columns_are_matched_by_name() {
+        let path = csv(
+            "columns",
+            "# comment\n Up , EAST,north,element,Orientation_deg\n\n0,1,2,pod,45\n0.5,3,4,,0\n",
+        );
+        let cfg = ArrayCfg::from_csv(&path, PosFrame::Enu, LengthUnit::M, None).unwrap();
+        assert_eq!(cfg.ants.len(), 2);
+        assert_eq!(cfg.ants[0].pos, (1.0, 2.0, 0.0));
+        assert_eq!(cfg.ants[1].pos, (3.0, 4.0, 0.5));
+        assert_eq!(cfg.ants[0].orientation_deg, 45.0);
+        assert_eq!(cfg.ants[0].element.as_deref(), Some("pod"));
+        assert_eq!(cfg.ants[1].element, None);
+        // the gains default to those of a working antenna
+        let g = &cfg.ants[1].gain;
+        assert!(g.weight == 1.0 && g.phase_deg == 0.0 && g.delay_ns == 0.0 && !g.flagged);
+
+        let path = csv("missing", "east,north\n1,2\n");
+        assert!(matches!(
+            ArrayCfg::from_csv(&path, PosFrame::Enu, LengthUnit::M, None),
+            Err(Error::MissingColumn { column, .. }) if column == "up"
+        ));
+        let path = csv("geodetic_header", "east,north,up\n1,2,3\n");
+        assert!(ArrayCfg::from_csv(&path, PosFrame::Geodetic, LengthUnit::M, None).is_err());
+        let path = csv("empty", "# nothing\n\n");
+        assert!(ArrayCfg::from_csv(&path, PosFrame::Enu, LengthUnit::M, None).is_err());
+    }
+
+    #[test]
+    fn csv_flags_and_units() {
+        let path = csv(
+            "flags",
+            "east,north,up,weight,phase_deg,delay_ns,flagged\n\
+             100,200,300,0.5,10,1.5,true\n\
+             100,200,300,1,0,0,1\n\
+             100,200,300,1,0,0,False\n\
+             100,200,300,1,0,0,0\n\
+             100,200,300,1,0,0,\n",
+        );
+        let cfg = ArrayCfg::from_csv(&path, PosFrame::Enu, LengthUnit::Cm, None).unwrap();
+        let flagged: Vec<_> = cfg.ants.iter().map(|a| a.gain.flagged).collect();
+        assert_eq!(flagged, [true, true, false, false, false]);
+        let g = &cfg.ants[0].gain;
+        assert!(g.weight == 0.5 && g.phase_deg == 10.0 && g.delay_ns == 1.5);
+
+        let cfg = cfg.to_enu().unwrap();
+        assert_eq!(cfg.units, LengthUnit::M);
+        let p = cfg.ants[0].pos;
+        assert!(
+            (p.0 - 1.0).abs() < 1e-12 && (p.1 - 2.0).abs() < 1e-12 && (p.2 - 3.0).abs() < 1e-12
+        );
+        let cfg = ArrayCfg::from_csv(&path, PosFrame::Enu, LengthUnit::Mm, None)
+            .unwrap()
+            .to_enu()
+            .unwrap();
+        assert!((cfg.ants[0].pos.0 - 0.1).abs() < 1e-12);
+
+        for bad in ["yes", "2"] {
+            let path = csv(
+                "bad_flag",
+                &format!("east,north,up,flagged\n0,0,0,{}\n", bad),
+            );
+            assert!(ArrayCfg::from_csv(&path, PosFrame::Enu, LengthUnit::M, None).is_err());
+        }
+        let path = csv("bad_number", "east,north,up\n0,x,0\n");
+        assert!(ArrayCfg::from_csv(&path, PosFrame::Enu, LengthUnit::M, None).is_err());
+    }
+
+    #[test]
+    fn ecef_and_geodetic_tables_are_centred_on_their_centroid() {
+        // a few antennas up to ~100 m from the site
+        let offsets = [
+            (0.0, 0.0, 0.0),
+            (1e-3, 0.0, 1.0),
+            (0.0, 8e-4, -1.0),
+            (-5e-4, -3e-4, 2.0),
+        ];
+        let s = site();
+        let geodetic: Vec<_> = offsets
+            .iter()
+            .map(|&(dlon, dlat, dh)| (s.lon_deg + dlon, s.lat_deg + dlat, s.height_m + dh))
+            .collect();
+        let ecef: Vec<_> = geodetic
+            .iter()
+            .map(|&(lon, lat, h)| geodetic_to_ecef(lon.to_radians(), lat.to_radians(), h))
+            .collect();
+
+        let tables = [
+            (
+                PosFrame::Geodetic,
+                LengthUnit::Mm,
+                geodetic
+                    .iter()
+                    .map(|p| format!("{:.12},{:.12},{:.3}", p.0, p.1, p.2 * 1e3))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                PosFrame::Ecef,
+                LengthUnit::M,
+                ecef.iter()
+                    .map(|p| format!("{:.6},{:.6},{:.6}", p.0, p.1, p.2))
+                    .collect(),
+            ),
+        ];
+        for (frame, units, rows) in tables {
+            let header = frame.columns().join(",");
+            let path = csv("frames", &format!("{}\n{}\n", header, rows.join("\n")));
+            let cfg = ArrayCfg::from_csv(&path, frame, units, None).unwrap();
+            // no site to refer the positions to
+            assert!(cfg.clone().to_enu().is_err());
+
+            let centroid = cfg.centroid_site().unwrap();
+            assert!((centroid.lon_deg - s.lon_deg).abs() < 1e-3);
+            assert!((centroid.lat_deg - s.lat_deg).abs() < 1e-3);
+            assert!((centroid.height_m - s.height_m).abs() < 2.0);
+            let enu = ArrayCfg {
+                site: Some(centroid),
+                ..cfg
+            }
+            .to_enu()
+            .unwrap();
+            assert_eq!(enu.frame, PosFrame::Enu);
+            assert!(enu.centroid_site().is_none());
+
+            let n = enu.ants.len() as f64;
+            let mean = enu.ants.iter().fold((0.0, 0.0, 0.0), |m, a| {
+                (m.0 + a.pos.0 / n, m.1 + a.pos.1 / n, m.2 + a.pos.2 / n)
+            });
+            assert!(mean.0.abs() < 1e-6 && mean.1.abs() < 1e-6 && mean.2.abs() < 1e-6);
+            // baselines keep their lengths, e.g., ~82 m to the east
+            let dist = |a: (f64, f64, f64), b: (f64, f64, f64)| {
+                ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt()
+            };
+            for i in 1..ecef.len() {
+                let d = dist(enu.ants[i].pos, enu.ants[0].pos);
+                assert!((d - dist(ecef[i], ecef[0])).abs() < 1e-5);
+            }
+            let east = enu.ants[1].pos.0 - enu.ants[0].pos.0;
+            assert!(east > 80.0 && east < 85.0);
+        }
+    }
 }
@@ -0,0 +1,436 @@
+pub mod ant_beam;
+pub mod antenna_temperature;
+pub mod array_beam_fullsky;
+pub mod array_beam_patch;
+pub mod array_beam_sky;
+pub mod beam_metrics;
+pub mod drift_scan;
+pub mod embedded_beam;
+pub mod grating_lobes;
+pub mod import;
+pub mod jones;
+pub mod layout;
+pub mod validate;
+pub mod visibility;
+
+use jm21cma::{
+    arbitrary_array::calc_steering_phases,
+    beam::{ArrayBeam, Beam, CrossedDipole, ElementBeams},
+    cfg::{ArrayCfg, ElementCfg, SiteCfg},
+    constants::SIDEREAL_RATE,
+    coordinates::AltAz,
+    error::{Error, Result},
+    single_ant_model::{FreqInterp, SingleAnt, SingleAntCube, SingleAntPol},
+    sky::{eq_to_enu, parse_utc, radec_to_vec3d, SkyFrame},
+    taper::{calc_taper_weights, Taper},
+};
+
+use scorus::{
+    coordinates::Vec3d,
+    healpix::{pix2ang_ring, utils::nside2npix},
+};
+
+use std::{collections::BTreeMap, f64::consts::PI, str::FromStr};
+
+#[derive(clap::Args, Debug)]
+pub struct ArrayArgs {
+    #[clap(short = 'c', long = "cfg", value_name = "array_cfg.yaml")]
+    pub cfg: String,
+
+    #[clap(flatten)]
+    pub steering: SteeringArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SteeringArgs {
+    #[clap(
+        long = "taper",
+        default_value = "uniform",
+        value_name = "uniform|hamming|gaussian:<edge dB>|taylor:<nbar>:<sll dB>|chebyshev:<sll dB>"
+    )]
+    pub taper: Taper,
+
+    #[clap(
+        long = "delay_step",
+        value_name = "delay line step in ns, steer with quantized delays instead of phases"
+    )]
+    pub delay_step: Option<f64>,
+
+    #[clap(long = "delay_bits", value_name = "delay line bits")]
+    pub delay_bits: Option<u32>,
+
+    #[clap(
+        long = "group_pointing",
+        num_args(1..),
+        value_name = "<zenith deg>:<az deg> of the delay lines of the groups of the cfg, one per level of groups, the last for the deeper ones; the phase center if not given"
+    )]
+    pub group_pointing: Vec<ZenithAz>,
+}
+
+/// direction given as zenith angle and azimuth (north=0, east=90) in deg
+#[derive(Clone, Copy, Debug)]
+pub struct ZenithAz(pub AltAz);
+
+impl FromStr for ZenithAz {
+    type Err = String;
+
+    /// `<zenith deg>:<az deg>`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse = |x: &str| {
+            x.trim()
+                .parse::<f64>()
+                .map_err(|e| format!("invalid direction {}: {}", s, e))
+        };
+        match s.split(':').collect::<Vec<_>>()[..] {
+            [zenith, az] => Ok(ZenithAz(AltAz::from_zenith_az(
+                parse(zenith)?.to_radians(),
+                parse(az)?.to_radians(),
+            ))),
+            _ => Err(format!(
+                "invalid direction {}, expected <zenith deg>:<az deg>",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct PointingArgs {
+    #[clap(
+        short = 'z',
+        long = "zenith0",
+        value_name = "phase center zenith angle in deg"
+    )]
+    pub zenith0: f64,
+
+    #[clap(
+        short = 'a',
+        long = "az0",
+        allow_hyphen_values = true,
+        value_name = "phase center az in deg, north=0, east=90"
+    )]
+    pub az0: f64,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FreqArgs {
+    #[clap(
+        short = 'A',
+        long = "ant_beam",
+        num_args(1..),
+        value_name = "ant beam in healpix, one file per frequency or a multi-HDU file"
+    )]
+    pub ant_beam_name: Vec<String>,
+
+    #[clap(
+        long = "freq_min",
+        requires_all = ["freq_max", "nfreq"],
+        value_name = "first channel in MHz, the beam files are used if not given"
+    )]
+    pub freq_min: Option<f64>,
+
+    #[clap(
+        long = "freq_max",
+        requires = "freq_min",
+        value_name = "last channel in MHz"
+    )]
+    pub freq_max: Option<f64>,
+
+    #[clap(long = "nfreq", requires = "freq_min", value_name = "num of channels")]
+    pub nfreq: Option<usize>,
+
+    #[clap(
+        long = "freq_interp",
+        default_value = "linear",
+        value_name = "linear|cubic"
+    )]
+    pub freq_interp: FreqInterp,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SkyArgs {
+    #[clap(short = 's', long = "nside", value_name = "nside of the output map")]
+    pub nside: usize,
+
+    #[clap(long = "frame", default_value = "eq", value_name = "eq|gal")]
+    pub frame: SkyFrame,
+}
+
+/// exactly one of `--lst` and `--utc`
+#[derive(clap::Args, Debug)]
+#[group(id = "time", required = true, multiple = false)]
+pub struct TimeArgs {
+    #[clap(long = "lst", value_name = "local sidereal time in hours")]
+    pub lst: Option<f64>,
+
+    #[clap(long = "utc", value_name = "YYYY-MM-DDThh:mm:ss")]
+    pub utc: Option<String>,
+}
+
+/// pointing fixed in the station frame or tracking a fixed ra/dec
+#[derive(clap::Args, Debug)]
+pub struct TrackingArgs {
+    #[clap(
+        short = 'z',
+        long = "zenith0",
+        requires = "az0",
+        required_unless_present = "ra0",
+        value_name = "fixed phase center zenith angle in deg"
+    )]
+    pub zenith0: Option<f64>,
+
+    #[clap(
+        short = 'a',
+        long = "az0",
+        requires = "zenith0",
+        allow_hyphen_values = true,
+        value_name = "fixed phase center az in deg, north=0, east=90"
+    )]
+    pub az0: Option<f64>,
+
+    #[clap(
+        long = "ra0",
+        requires = "dec0",
+        conflicts_with = "zenith0",
+        value_name = "tracked phase center ra in deg"
+    )]
+    pub ra0: Option<f64>,
+
+    #[clap(
+        long = "dec0",
+        requires = "ra0",
+        allow_hyphen_values = true,
+        value_name = "tracked phase center dec in deg"
+    )]
+    pub dec0: Option<f64>,
+}
+
+impl TrackingArgs {
+    /// phase center at local sidereal time `lst` for a station at latitude `lat` (in rad)
+    pub fn altaz(&self, lst: f64, lat: f64) -> AltAz {
+        match (self.zenith0, self.az0, self.ra0, self.dec0) {
+            (Some(zenith0), Some(az0), _, _) => {
+                AltAz::from_zenith_az(zenith0.to_radians(), az0.to_radians())
+            }
+            (_, _, Some(ra0), Some(dec0)) => AltAz::from_vec3d(&eq_to_enu(
+                &radec_to_vec3d(ra0.to_radians(), dec0.to_radians()),
+                lst,
+                lat,
+            )),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// time steps of a scan starting at the time given by `TimeArgs`
+#[derive(clap::Args, Debug)]
+pub struct ScanArgs {
+    #[clap(long = "ntime", default_value = "1", value_name = "num of time steps")]
+    pub ntime: usize,
+
+    #[clap(long = "cadence", default_value = "60", value_name = "time step in s")]
+    pub cadence: f64,
+}
+
+impl ScanArgs {
+    /// local sidereal times of the steps in rad
+    pub fn lsts(&self, lst0: f64) -> Vec<f64> {
+        let dlst = self.cadence * SIDEREAL_RATE * 2.0 * PI / 86400.0;
+        (0..self.ntime)
+            .map(|i| (lst0 + i as f64 * dlst).rem_euclid(2.0 * PI))
+            .collect()
+    }
+}
+
+impl TimeArgs {
+    /// local sidereal time in rad
+    pub fn lst(&self, site: &SiteCfg) -> Result<f64> {
+        match (self.lst, &self.utc) {
+            (Some(lst), _) => Ok((lst / 12.0 * PI).rem_euclid(2.0 * PI)),
+            (None, Some(utc)) => Ok(site.lst(parse_utc(utc).map_err(Error::Invalid)?)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl FreqArgs {
+    pub fn load(&self) -> Result<SingleAntCube> {
+        SingleAntCube::from_fits(&self.ant_beam_name)
+    }
+
+    /// channels in MHz, which must be within the range of the beam files
+    pub fn freqs_mhz(&self, cube: &SingleAntCube) -> Result<Vec<f64>> {
+        let freqs = match (self.freq_min, self.freq_max, self.nfreq) {
+            (_, _, Some(0)) => {
+                return Err(Error::Invalid("--nfreq must be at least 1".to_string()))
+            }
+            (Some(f1), Some(f2), Some(n)) if n > 1 => (0..n)
+                .map(|i| f1 + (f2 - f1) * i as f64 / (n - 1) as f64)
+                .collect(),
+            (Some(f1), _, _) => vec![f1],
+            _ => return Ok(cube.freqs_MHz()),
+        };
+        let beam_freqs = cube.freqs_MHz();
+        let (lo, hi) = (beam_freqs[0], beam_freqs[beam_freqs.len() - 1]);
+        let eps = 1e-9 * (hi - lo).max(1.0);
+        if freqs.iter().any(|f| !(*f >= lo - eps && *f <= hi + eps)) {
+            return Err(Error::Invalid(format!(
+                "--freq_min and --freq_max must be within [{}, {}] MHz, the range of the beam files",
+                lo, hi
+            )));
+        }
+        Ok(freqs)
+    }
+}
+
+impl PointingArgs {
+    pub fn altaz(&self) -> AltAz {
+        AltAz::from_zenith_az(self.zenith0.to_radians(), self.az0.to_radians())
+    }
+}
+
+/// equatorial unit vectors of the pixels of a healpix map in `frame`
+pub fn sky_dirs(nside: usize, frame: SkyFrame) -> Vec<Vec3d<f64>> {
+    (0..nside2npix(nside))
+        .map(|ipix| frame.to_eq(&Vec3d::from_sph_coord(pix2ang_ring::<f64>(nside, ipix))))
+        .collect()
+}
+
+/// station frame directions of equatorial unit vectors, None below the horizon
+pub fn station_dirs(sky_dirs: &[Vec3d<f64>], lst: f64, lat: f64) -> Vec<Option<AltAz>> {
+    sky_dirs
+        .iter()
+        .map(|v| {
+            let v = eq_to_enu(v, lst, lat);
+            if v.z > 0.0 {
+                Some(AltAz::from_vec3d(&v))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// element of the beam files, with the E field if present, otherwise with the
+/// power pattern and the polarization of an idealized log-periodic antenna
+pub fn default_element<'a>(
+    ant_beam: &'a SingleAnt,
+    pol_beam: Option<&'a SingleAntPol>,
+) -> &'a dyn Beam {
+    match pol_beam {
+        Some(pol_beam) => pol_beam,
+        None => ant_beam,
+    }
+}
+
+/// element beam named in the array cfg, for all frequencies
+pub enum ElementModel {
+    Cube(SingleAntCube),
+    Dipole(CrossedDipole),
+}
+
+impl ElementModel {
+    pub fn from_cfg(cfg: &ElementCfg) -> Result<Self> {
+        match cfg {
+            ElementCfg::Beam { files } => Ok(ElementModel::Cube(SingleAntCube::from_fits(files)?)),
+            &ElementCfg::Dipole { length_m } => {
+                Ok(ElementModel::Dipole(CrossedDipole { length: length_m }))
+            }
+        }
+    }
+
+    /// the E field beam if the files hold it, otherwise the power beam
+    pub fn at_freq(&self, freq_mhz: f64, interp: FreqInterp) -> Result<Box<dyn Beam>> {
+        Ok(match self {
+            ElementModel::Cube(cube) => match cube.pol_at_freq(freq_mhz, interp)? {
+                Some(pol) => Box::new(pol),
+                None => Box::new(cube.power_at_freq(freq_mhz, interp)?),
+            },
+            ElementModel::Dipole(d) => Box::new(*d),
+        })
+    }
+}
+
+/// the array with its weights and steering settings
+pub struct Array {
+    pub cfg: ArrayCfg,
+    pub w_list: Vec<f64>,
+    /// resolution (in s) and number of control bits of the delay lines,
+    /// ideal phase steering if None
+    pub delay_lines: Option<(f64, Option<u32>)>,
+    /// steering of the groups of the cfg level by level, see
+    /// `ArrayBeam::with_group_steering`
+    pub group_steering: Vec<AltAz>,
+    /// the element beams of the cfg by name
+    pub elements: BTreeMap<String, ElementModel>,
+}
+
+impl Array {
+    pub fn new(args: &ArrayArgs) -> Result<Self> {
+        let cfg = ArrayCfg::from_yaml(&args.cfg)?;
+        Self::from_cfg(cfg, &args.steering)
+    }
+
+    pub fn from_cfg(cfg: ArrayCfg, steering: &SteeringArgs) -> Result<Self> {
+        let w_list = calc_taper_weights(&cfg, &steering.taper);
+        let elements = cfg
+            .elements
+            .iter()
+            .map(|(name, e)| Ok((name.clone(), ElementModel::from_cfg(e)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            cfg,
+            w_list,
+            delay_lines: steering
+                .delay_step
+                .map(|step| (step * 1e-9, steering.delay_bits)),
+            group_steering: steering.group_pointing.iter().map(|d| d.0).collect(),
+            elements,
+        })
+    }
+
+    /// beams of the named elements at freq_mhz
+    pub fn elements_at(&self, freq_mhz: f64, interp: FreqInterp) -> Result<ElementBeams> {
+        self.elements
+            .iter()
+            .map(|(name, e)| Ok((name.clone(), e.at_freq(freq_mhz, interp)?)))
+            .collect()
+    }
+
+    pub fn site(&self) -> Result<&SiteCfg> {
+        self.cfg
+            .site
+            .as_ref()
+            .ok_or_else(|| Error::Invalid("the array cfg has no site location".to_string()))
+    }
+
+    /// steering phases plus the instrumental phases of the antennas at freq (in Hz)
+    pub fn phases(&self, pointing: &AltAz, freq: f64) -> Vec<f64> {
+        calc_steering_phases(
+            &self.cfg,
+            pointing,
+            &self.group_steering,
+            freq,
+            self.delay_lines,
+        )
+    }
+
+    /// the array beam steered towards pointing, `element` being the default
+    /// element and the named elements of the cfg those at freq_mhz
+    pub fn beam<'a>(
+        &self,
+        element: &'a dyn Beam,
+        freq_mhz: f64,
+        interp: FreqInterp,
+        pointing: &AltAz,
+    ) -> Result<ArrayBeam<&'a dyn Beam>> {
+        let beam = ArrayBeam::new(element, self.cfg.clone(), *pointing)
+            .with_weights(self.w_list.clone())
+            .with_group_steering(self.group_steering.clone())
+            .with_elements(self.elements_at(freq_mhz, interp)?);
+        Ok(match self.delay_lines {
+            Some((step, nbits)) => beam.with_delay_lines(step, nbits),
+            None => beam,
+        })
+    }
+}
@@ -1,5 +1,3 @@
-#![cfg(not(target_family = "wasm"))]
-#![allow(non_snake_case)]
 use std::fs::read_to_string;
 
 use pest::Parser;
@@ -8,6 +6,8 @@ use num::traits::FloatConst;
 
 use healpix_fits::write_map;
 
+use jm21cma::error::{Error, Result, WithPath};
+
 use scorus::{
     coordinates::SphCoord,
     healpix::{interp::get_interpol_ring, utils::nside2npix},
@@ -15,36 +15,39 @@ use scorus::{
 
 use necrs::nec_parser::{parse_nec_file, NecParser, Rule};
 
-#[derive(clap::Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
-struct Args {
-    /// config
-    #[clap(short = 'n', long = "nec", value_name="nec file")]
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(short = 'n', long = "nec", value_name = "nec file")]
     nec: String,
 
-    #[clap(short = 's', long = "nside", value_name="nside")]
+    #[clap(short = 's', long = "nside", value_name = "nside")]
     nside: usize,
 
-    #[clap(short = 'f', long = "freq_MHz", value_name="freq in MHz")]
-    freq_MHz: f64,
+    #[clap(short = 'f', long = "freq", value_name = "freq in MHz")]
+    freq_mhz: f64,
 
-    #[clap(short = 'o', long = "out", value_name="out file")]
+    #[clap(short = 'o', long = "out", value_name = "out file")]
     outfile: String,
-
-
 }
 
-pub fn main() {
-    let args=<Args as clap::Parser>::parse();    
-
-    let nec_file_name = &args.nec;
-    let freq = args.freq_MHz;
-    let nside = args.nside;
-    let out_file_name = &args.outfile;
+pub fn run(args: Args) -> Result<()> {
+    let deck = read_to_string(&args.nec).with_path(&args.nec)?;
+    write_nec_beam(&deck, &args.nec, args.nside, args.freq_mhz, &args.outfile)
+}
 
+/// simulates the NEC `deck` (read from `nec_file_name`) at `freq` in MHz and
+/// writes the power pattern; necrs only gives the gain, so no `EFIELD`
+/// extension is written
+pub fn write_nec_beam(
+    deck: &str,
+    nec_file_name: &str,
+    nside: usize,
+    freq: f64,
+    out_file_name: &str,
+) -> Result<()> {
     let mut context = parse_nec_file(
-        NecParser::parse(Rule::NecFile, &read_to_string(nec_file_name).unwrap())
-            .unwrap()
+        NecParser::parse(Rule::NecFile, deck)
+            .map_err(|e| Error::Invalid(format!("{}: {}", nec_file_name, e)))?
             .next()
             .unwrap(),
     );
@@ -53,9 +56,8 @@ pub fn main() {
 
     let npix = nside2npix(nside);
     let angular_resolution = (4.0 * f64::PI() / npix as f64).sqrt().to_degrees();
-    println!("{}", angular_resolution);
+    eprintln!("pixel size {:.3} deg", angular_resolution);
 
-    //context.nec_rp_card(0, ntheta as i32, nphi as i32, 1, 0, 0, 0, 0.0, 0.0, dtheta, dphi, 0.0, 0.0);
     let (thetas, phis) = context.rp_from_npix(npix * 4, 0, 1, 0, 0, 0, 0.0, 0.0);
     let mut data = vec![0.0; npix];
     let mut wgt = vec![0.0; npix];
@@ -80,9 +82,11 @@ pub fn main() {
         }
     }
 
-    let s=data.iter().cloned().sum::<f64>();
-    data.iter_mut().for_each(|x| *x/=s);
+    let s = data.iter().cloned().sum::<f64>();
+    data.iter_mut().for_each(|x| *x /= s);
 
-    let (mut fitsfile, hdu)=write_map(out_file_name, &[&data], false, true);
-    hdu.write_key(&mut fitsfile, "FREQ_MHZ", freq).unwrap();
+    let (mut fitsfile, hdu) = write_map(out_file_name, &[&data], false, true);
+    hdu.write_key(&mut fitsfile, "FREQ_MHZ", freq)
+        .with_path(out_file_name)?;
+    Ok(())
 }
@@ -0,0 +1,147 @@
+use std::fs::remove_file;
+
+use fitsio::{
+    images::{ImageDescription, ImageType},
+    FitsFile,
+};
+
+use jm21cma::{
+    beam::Beam,
+    error::{Error, Result, WithPath},
+    parallel::par_map,
+    sky_model::{antenna_temperature, SkyCube},
+};
+
+use ndarray::Array2;
+
+use super::{sky_dirs, station_dirs, Array, ArrayArgs, FreqArgs, ScanArgs, TimeArgs, TrackingArgs};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    array: ArrayArgs,
+
+    #[clap(flatten)]
+    tracking: TrackingArgs,
+
+    #[clap(flatten)]
+    freqs: FreqArgs,
+
+    #[clap(
+        long = "sky",
+        value_name = "sky brightness temperature in healpix, one column per frequency"
+    )]
+    sky: String,
+
+    #[clap(flatten)]
+    time: TimeArgs,
+
+    #[clap(flatten)]
+    scan: ScanArgs,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out fits, a time x freq image of the antenna temperature in K"
+    )]
+    outfile: String,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let array = Array::new(&args.array)?;
+    let site = array.site()?;
+    let lat = site.lat_deg.to_radians();
+    let lst0 = args.time.lst(site)?;
+    let lsts = args.scan.lsts(lst0);
+
+    let cube = args.freqs.load()?;
+    let freqs_mhz = args.freqs.freqs_mhz(&cube)?;
+    let ant_beams: Vec<_> = freqs_mhz
+        .iter()
+        .map(|&f| cube.power_at_freq(f, args.freqs.freq_interp))
+        .collect::<Result<_>>()?;
+    // steered towards the phase center of each time step in turn
+    let mut beams: Vec<_> = freqs_mhz
+        .iter()
+        .zip(ant_beams.iter())
+        .map(|(&f, ant_beam)| {
+            array.beam(
+                ant_beam,
+                f,
+                args.freqs.freq_interp,
+                &args.tracking.altaz(lst0, lat),
+            )
+        })
+        .collect::<Result<_>>()?;
+
+    // the beam is evaluated on the pixels of the sky model
+    let sky = SkyCube::from_fits(&args.sky)?;
+    let sky_maps: Vec<_> = freqs_mhz
+        .iter()
+        .map(|&f| sky.map_at_freq(f, args.freqs.freq_interp))
+        .collect::<Result<_>>()?;
+    let sky_dirs = sky_dirs(sky.nside, sky.frame);
+
+    let mut t_ant = Array2::<f64>::zeros((lsts.len(), freqs_mhz.len()));
+    for (t_idx, &lst) in lsts.iter().enumerate() {
+        let ptg = args.tracking.altaz(lst, lat);
+        let dirs = station_dirs(&sky_dirs, lst, lat);
+        beams.iter_mut().for_each(|b| b.pointing = ptg);
+        // the frequencies in parallel, and the pixels of each
+        let temps = par_map(0..beams.len(), |f_idx| {
+            let beam = beams[f_idx].power_many(&dirs, freqs_mhz[f_idx] * 1e6);
+            antenna_temperature(&beam, &sky_maps[f_idx]).map_err(|e| {
+                Error::Invalid(format!(
+                    "lst={} h, {} MHz: {}",
+                    lst.to_degrees() / 15.0,
+                    freqs_mhz[f_idx],
+                    e
+                ))
+            })
+        });
+        for (f_idx, t) in temps.into_iter().enumerate() {
+            t_ant[(t_idx, f_idx)] = t?;
+        }
+        println!(
+            "lst={} h, T_ant={:?} K",
+            lst.to_degrees() / 15.0,
+            t_ant.row(t_idx).to_vec()
+        );
+    }
+
+    let image_description = ImageDescription {
+        data_type: ImageType::Double,
+        dimensions: &[lsts.len(), freqs_mhz.len()],
+    };
+    let _ = remove_file(&args.outfile);
+    let mut output_fits = FitsFile::create(&args.outfile)
+        .with_custom_primary(&image_description)
+        .open()
+        .with_path(&args.outfile)?;
+    let hdu = output_fits.primary_hdu().with_path(&args.outfile)?;
+    hdu.write_image(&mut output_fits, t_ant.as_slice().unwrap())
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "BUNIT", "K")
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CTYPE2", "TIME")
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CRPIX2", 1)
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CRVAL2", 0.0)
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CDELT2", args.scan.cadence)
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CUNIT2", "s")
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "LST0", lst0.to_degrees() / 15.0)
+        .with_path(&args.outfile)?;
+    if let Some(utc) = &args.time.utc {
+        hdu.write_key(&mut output_fits, "DATE-OBS", utc.as_str())
+            .with_path(&args.outfile)?;
+    }
+    for (i, &freq_mhz) in freqs_mhz.iter().enumerate() {
+        hdu.write_key(&mut output_fits, &format!("FREQ{}", i + 1), freq_mhz * 1e6)
+            .with_path(&args.outfile)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use healpix_fits::write_map;
+
+use jm21cma::{
+    beam::Beam,
+    coordinates::AltAz,
+    error::{Result, WithPath},
+    parallel::par_map,
+};
+
+use scorus::{coordinates::Vec3d, healpix::pix2ang_ring};
+
+use super::{Array, ArrayArgs, FreqArgs, PointingArgs};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    array: ArrayArgs,
+
+    #[clap(flatten)]
+    pointing: PointingArgs,
+
+    #[clap(flatten)]
+    freqs: FreqArgs,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out healpix fits, one column per frequency"
+    )]
+    outfile: String,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let array = Array::new(&args.array)?;
+    let ptg = args.pointing.altaz();
+
+    let cube = args.freqs.load()?;
+
+    let freqs_mhz = args.freqs.freqs_mhz(&cube)?;
+    let freqs: Vec<_> = freqs_mhz.iter().map(|f| f * 1e6).collect();
+    eprintln!("freq={:?} MHz", freqs_mhz);
+    // the frequencies in parallel, and the pixels of each
+    let maps = par_map(0..freqs.len(), |i| -> Result<Vec<f64>> {
+        let (freq_mhz, freq) = (freqs_mhz[i], freqs[i]);
+        let ant_beam = cube.power_at_freq(freq_mhz, args.freqs.freq_interp)?;
+        let nside = ant_beam.nside;
+        let sphs: Vec<_> = (0..ant_beam.data.len())
+            .map(|ipix| pix2ang_ring::<f64>(nside, ipix))
+            .collect();
+        let beam = array.beam(&ant_beam, freq_mhz, args.freqs.freq_interp, &ptg)?;
+        // the element pattern is taken at the pixels rather than interpolated
+        Ok(if array.cfg.has_identical_elements() {
+            let dirs: Vec<_> = sphs.iter().map(|&sph| Vec3d::from_sph_coord(sph)).collect();
+            beam.factor(freq)
+                .eval_many(&dirs)
+                .iter()
+                .zip(ant_beam.data.iter())
+                .map(|(af, &ant_pattern)| ant_pattern * af.norm_sqr())
+                .collect()
+        } else {
+            let dirs: Vec<_> = sphs
+                .iter()
+                .map(|sph| Some(AltAz::from_sph_coord(sph)))
+                .collect();
+            beam.power_many(&dirs, freq)
+        })
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+
+    let columns: Vec<&[f64]> = maps.iter().map(|m| &m[..]).collect();
+    let (mut fitsfile, hdu) = write_map(&args.outfile, &columns, false, true);
+    for (i, &freq) in freqs.iter().enumerate() {
+        hdu.write_key(&mut fitsfile, &format!("FREQ{}", i + 1), freq)
+            .with_path(&args.outfile)?;
+    }
+    Ok(())
+}
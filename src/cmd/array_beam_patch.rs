@@ -0,0 +1,263 @@
+use fitsio::{
+    images::{ImageDescription, ImageType},
+    FitsFile,
+};
+
+use jm21cma::{
+    beam::Beam,
+    coordinates::AltAz,
+    error::{Error, Result, WithPath},
+    parallel::par_map,
+    projection::{PatchFrame, Projection, TangentFrame},
+    sky::{enu_to_eq, eq_to_enu},
+};
+
+use ndarray::{s, Array4, ArrayView3};
+
+use num::complex::Complex;
+
+use std::{f64::consts::PI, fs::remove_file};
+
+use super::{default_element, Array, ArrayArgs, FreqArgs, PointingArgs, TimeArgs};
+
+#[derive(clap::Args, Debug)]
+#[command(mut_group("time", |g| g.required(false)))]
+pub struct Args {
+    #[clap(flatten)]
+    array: ArrayArgs,
+
+    #[clap(flatten)]
+    pointing: PointingArgs,
+
+    #[clap(flatten)]
+    freqs: FreqArgs,
+
+    #[clap(short = 'w', long = "fov_width", value_name = "fov_width in deg")]
+    fov_w_deg: f64,
+
+    #[clap(short = 'p', long = "fov_pix", value_name = "width in npix")]
+    fovw_pix: usize,
+
+    #[clap(
+        long = "proj",
+        default_value = "sin",
+        value_name = "sin|tan|arc|zea|car"
+    )]
+    proj: Projection,
+
+    #[clap(
+        long = "frame",
+        default_value = "altaz",
+        requires_ifs = [("eq", "time"), ("gal", "time")],
+        value_name = "altaz|eq|gal, eq and gal need --lst or --utc"
+    )]
+    frame: PatchFrame,
+
+    /// only for the eq and gal frames
+    #[clap(flatten)]
+    time: Option<TimeArgs>,
+
+    #[clap(short = 'o', long = "out", value_name = "out prefix")]
+    out_prefix: String,
+}
+
+/// celestial part of the WCS of the patch
+struct Wcs {
+    ctype: (String, String),
+    crval: (f64, f64),
+    cdelt: (f64, f64),
+    crpix: f64,
+}
+
+fn write_fits(
+    fname: &str,
+    efield_pattern: ArrayView3<f64>,
+    wcs: &Wcs,
+    freq0: f64,
+    dfreq: f64,
+) -> Result<()> {
+    let nfreq = efield_pattern.shape()[0];
+    let fovw_pix = efield_pattern.shape()[1];
+
+    let image_description = ImageDescription {
+        data_type: ImageType::Double,
+        dimensions: &[nfreq, fovw_pix, fovw_pix],
+    };
+    let _ = remove_file(fname);
+    let mut output_fits = FitsFile::create(fname)
+        .with_custom_primary(&image_description)
+        .open()
+        .with_path(fname)?;
+
+    let hdu = output_fits.primary_hdu().with_path(fname)?;
+    hdu.write_image(&mut output_fits, efield_pattern.as_slice().unwrap())
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CTYPE1", wcs.ctype.0.as_str())
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CRPIX1", wcs.crpix)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CDELT1", wcs.cdelt.0)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CRVAL1", wcs.crval.0)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CUNIT1", "deg")
+        .with_path(fname)?;
+
+    hdu.write_key(&mut output_fits, "CTYPE2", wcs.ctype.1.as_str())
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CRPIX2", wcs.crpix)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CDELT2", wcs.cdelt.1)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CRVAL2", wcs.crval.1)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CUNIT2", "deg")
+        .with_path(fname)?;
+
+    if wcs.ctype.0.starts_with("RA") {
+        hdu.write_key(&mut output_fits, "RADESYS", "FK5")
+            .with_path(fname)?;
+        hdu.write_key(&mut output_fits, "EQUINOX", 2000.0)
+            .with_path(fname)?;
+    }
+
+    hdu.write_key(&mut output_fits, "CTYPE3", "FREQ")
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CRPIX3", 1)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CRVAL3", freq0)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CDELT3", dfreq)
+        .with_path(fname)?;
+    hdu.write_key(&mut output_fits, "CUNIT3", "Hz")
+        .with_path(fname)?;
+    Ok(())
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let array = Array::new(&args.array)?;
+
+    let cube = args.freqs.load()?;
+    let freqs_mhz = args.freqs.freqs_mhz(&cube)?;
+
+    // the jones terms in the order of `element_jones`, written as xx, xy, yx and yy
+    let mut jones_pattern =
+        Array4::<Complex<f64>>::zeros((4, freqs_mhz.len(), args.fovw_pix, args.fovw_pix));
+
+    let ptg = args.pointing.altaz();
+
+    // the reference pixel is the center of the patch, the longitude of the sky
+    // frames increases to the left
+    let dx = args.fov_w_deg / args.fovw_pix as f64;
+    let crpix = (args.fovw_pix / 2) as f64 + 1.0;
+    let (tangent_frame, lon_lat, cdelt1, to_enu): (_, _, _, Box<dyn Fn(_) -> _>) = match args.frame
+    {
+        PatchFrame::AltAz => (
+            TangentFrame::from_altaz(&ptg),
+            (ptg.az, ptg.alt),
+            dx,
+            Box::new(|v| v),
+        ),
+        PatchFrame::Sky(sky_frame) => {
+            let array_site = array.site()?;
+            let lat = array_site.lat_deg.to_radians();
+            // required by --frame
+            let lst = args.time.as_ref().unwrap().lst(array_site)?;
+            let v = sky_frame.from_eq(&enu_to_eq(&ptg.to_vec3d(), lst, lat));
+            let lon = v.y.atan2(v.x).rem_euclid(2.0 * PI);
+            let lat0 = v.z.clamp(-1.0, 1.0).asin();
+            (
+                TangentFrame::from_lon_lat(lon, lat0),
+                (lon, lat0),
+                -dx,
+                Box::new(move |v| eq_to_enu(&sky_frame.to_eq(&v), lst, lat)),
+            )
+        }
+    };
+    let (ctype1, ctype2) = args.frame.ctypes(args.proj);
+    let wcs = Wcs {
+        ctype: (ctype1, ctype2),
+        crval: (lon_lat.0.to_degrees(), lon_lat.1.to_degrees()),
+        cdelt: (cdelt1, dx),
+        crpix,
+    };
+
+    // station frame direction of every pixel, row by row, None outside of the
+    // projection or below the horizon
+    let dirs: Vec<_> = (0..args.fovw_pix * args.fovw_pix)
+        .map(|i| {
+            let (iy, ix) = (i / args.fovw_pix, i % args.fovw_pix);
+            let y = ((iy + 1) as f64 - crpix) * wcs.cdelt.1;
+            let x = ((ix + 1) as f64 - crpix) * wcs.cdelt.0;
+            args.proj
+                .deproject(x.to_radians(), y.to_radians())
+                .map(|c| to_enu(tangent_frame.to_vec3d(&c)))
+                .filter(|v| v.z > 0.0)
+                .map(|v| AltAz::from_vec3d(&v))
+        })
+        .collect();
+
+    let freq0 = freqs_mhz[0] * 1e6;
+    let dfreq = freqs_mhz.get(1).map_or(0.0, |f| f * 1e6 - freq0);
+    eprintln!("freq={:?} MHz", freqs_mhz);
+    // the frequencies in parallel, and the pixels of each
+    let patches = par_map(0..freqs_mhz.len(), |ifreq| {
+        let freq_mhz = freqs_mhz[ifreq];
+        let ant_beam = cube.power_at_freq(freq_mhz, args.freqs.freq_interp)?;
+        // fall back to the idealized polarization if the beam files hold no E field
+        let pol_beam = cube.pol_at_freq(freq_mhz, args.freqs.freq_interp)?;
+        let beam = array.beam(
+            default_element(&ant_beam, pol_beam.as_ref()),
+            freq_mhz,
+            args.freqs.freq_interp,
+            &ptg,
+        )?;
+        Ok(beam.jones_many(&dirs, freq_mhz * 1e6))
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+
+    for (ifreq, pixels) in patches.iter().enumerate() {
+        // peak power of the x feed
+        let mut beam_max = 0.0;
+        for (i, jones) in pixels.iter().enumerate() {
+            let Some(jones) = jones else { continue };
+            let (iy, ix) = (i / args.fovw_pix, i % args.fovw_pix);
+            for (k, &j) in jones.iter().enumerate() {
+                jones_pattern[(k, ifreq, iy, ix)] = j;
+            }
+            let total_beam = jones[0].norm_sqr() + jones[1].norm_sqr();
+            if beam_max < total_beam {
+                beam_max = total_beam;
+            }
+        }
+
+        if beam_max == 0.0 {
+            return Err(Error::Invalid(format!(
+                "the beam of the x feed is zero on the whole patch at {} MHz, e.g. below the horizon",
+                freqs_mhz[ifreq]
+            )));
+        }
+        let norm = beam_max.sqrt();
+        jones_pattern
+            .slice_mut(s![.., ifreq, .., ..])
+            .iter_mut()
+            .for_each(|x| {
+                *x /= norm;
+            });
+    }
+
+    for (k, name) in ["xx", "xy", "yx", "yy"].iter().enumerate() {
+        let term = jones_pattern.slice(s![k, .., .., ..]);
+        for (part, value) in [("re", term.mapv(|x| x.re)), ("im", term.mapv(|x| x.im))] {
+            write_fits(
+                &format!("{}_{}_{}.fits", args.out_prefix, name, part),
+                value.view(),
+                &wcs,
+                freq0,
+                dfreq,
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use healpix_fits::write_map;
+
+use jm21cma::{
+    beam::Beam,
+    error::{Result, WithPath},
+    parallel::par_map,
+};
+
+use super::{sky_dirs, station_dirs, Array, ArrayArgs, FreqArgs, PointingArgs, SkyArgs, TimeArgs};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    array: ArrayArgs,
+
+    #[clap(flatten)]
+    pointing: PointingArgs,
+
+    #[clap(flatten)]
+    freqs: FreqArgs,
+
+    #[clap(flatten)]
+    sky: SkyArgs,
+
+    #[clap(flatten)]
+    time: TimeArgs,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out healpix fits, one column per frequency"
+    )]
+    outfile: String,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let array = Array::new(&args.array)?;
+    let ptg = args.pointing.altaz();
+    let site = array.site()?;
+    let lat = site.lat_deg.to_radians();
+    let lst = args.time.lst(site)?;
+    eprintln!("lst={} h", lst.to_degrees() / 15.0);
+
+    let cube = args.freqs.load()?;
+    let dirs = station_dirs(&sky_dirs(args.sky.nside, args.sky.frame), lst, lat);
+
+    let freqs_mhz = args.freqs.freqs_mhz(&cube)?;
+    let freqs: Vec<_> = freqs_mhz.iter().map(|f| f * 1e6).collect();
+    eprintln!("freq={:?} MHz", freqs_mhz);
+    // the frequencies in parallel, and the pixels of each
+    let maps = par_map(0..freqs.len(), |i| -> Result<Vec<f64>> {
+        let ant_beam = cube.power_at_freq(freqs_mhz[i], args.freqs.freq_interp)?;
+        let beam = array.beam(&ant_beam, freqs_mhz[i], args.freqs.freq_interp, &ptg)?;
+        Ok(beam.power_many(&dirs, freqs[i]))
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+
+    let columns: Vec<&[f64]> = maps.iter().map(|m| &m[..]).collect();
+    let (mut fitsfile, hdu) = write_map(&args.outfile, &columns, false, true);
+    hdu.write_key(&mut fitsfile, "COORDSYS", args.sky.frame.coordsys())
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut fitsfile, "LST", lst.to_degrees() / 15.0)
+        .with_path(&args.outfile)?;
+    for (i, &freq) in freqs.iter().enumerate() {
+        hdu.write_key(&mut fitsfile, &format!("FREQ{}", i + 1), freq)
+            .with_path(&args.outfile)?;
+    }
+    Ok(())
+}
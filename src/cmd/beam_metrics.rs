@@ -0,0 +1,315 @@
+#![allow(non_snake_case)]
+
+use std::{f64::consts::PI, fs::File, io::Write, str::FromStr};
+
+use fitsio::hdu::HduInfo;
+
+use jm21cma::{
+    beam_metrics::{calc_beam_metrics, BeamMetrics},
+    constants::LIGHT_SPEED as C,
+    coordinates::AltAz,
+    error::{open_fits, read_key, Error, Result, WithPath},
+    projection::{PatchFrame, TangentFrame},
+    sky::SkyFrame,
+    sky_model::read_healpix_cube,
+};
+
+use scorus::{
+    coordinates::Vec3d,
+    healpix::{npix2nside, pix2ang_ring},
+};
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Yaml,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "yaml" => Ok(Format::Yaml),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format {}, use yaml or csv", s)),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(
+        long = "healpix",
+        required_unless_present = "patch",
+        conflicts_with = "patch",
+        value_name = "power beam in healpix, one column per frequency"
+    )]
+    healpix: Option<String>,
+
+    #[clap(
+        long = "patch",
+        value_name = "out prefix of array-beam-patch, the power of the x feed is |xx|^2 + |xy|^2"
+    )]
+    patch: Option<String>,
+
+    #[clap(long = "format", default_value = "yaml", value_name = "yaml|csv")]
+    format: Format,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out file, stdout if not given"
+    )]
+    outfile: Option<String>,
+}
+
+/// how the sample vectors are expressed, to report the peak direction
+#[derive(Clone, Copy, Debug)]
+enum Frame {
+    /// east-north-up, reported as azimuth (east of north) and altitude
+    AltAz,
+    Sky(SkyFrame),
+}
+
+impl Frame {
+    fn name(&self) -> &'static str {
+        match self {
+            Frame::AltAz => "altaz",
+            Frame::Sky(SkyFrame::Equatorial) => "eq",
+            Frame::Sky(SkyFrame::Galactic) => "gal",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    freq_MHz: f64,
+    frame: &'static str,
+    peak_lon_deg: f64,
+    peak_lat_deg: f64,
+    fwhm_major_deg: f64,
+    fwhm_minor_deg: f64,
+    /// from the pole of the frame (zenith, celestial or galactic north, up)
+    /// towards increasing longitude
+    fwhm_pa_deg: f64,
+    first_null_deg: f64,
+    sidelobe_db: f64,
+    solid_angle_sr: f64,
+    main_beam_solid_angle_sr: f64,
+    main_beam_efficiency: f64,
+    directivity_dbi: f64,
+    effective_area_m2: f64,
+}
+
+const CSV_HEADER: &str = "freq_MHz,frame,peak_lon_deg,peak_lat_deg,fwhm_major_deg,fwhm_minor_deg,fwhm_pa_deg,first_null_deg,sidelobe_db,solid_angle_sr,main_beam_solid_angle_sr,main_beam_efficiency,directivity_dbi,effective_area_m2";
+
+impl Report {
+    fn new(m: &BeamMetrics, freq_MHz: f64, frame: Frame) -> Self {
+        let p = &m.peak_dir;
+        let (lon, lat, pa) = match frame {
+            Frame::AltAz => {
+                // azimuth runs the other way round
+                let altaz = AltAz::from_vec3d(p);
+                (altaz.az, altaz.alt, (PI - m.fwhm_pa).rem_euclid(PI))
+            }
+            Frame::Sky(_) => (
+                p.y.atan2(p.x).rem_euclid(2.0 * PI),
+                p.z.clamp(-1.0, 1.0).asin(),
+                m.fwhm_pa,
+            ),
+        };
+        Self {
+            freq_MHz,
+            frame: frame.name(),
+            peak_lon_deg: lon.to_degrees(),
+            peak_lat_deg: lat.to_degrees(),
+            fwhm_major_deg: m.fwhm_major.to_degrees(),
+            fwhm_minor_deg: m.fwhm_minor.to_degrees(),
+            fwhm_pa_deg: pa.to_degrees(),
+            first_null_deg: m.first_null.to_degrees(),
+            sidelobe_db: 10.0 * m.sidelobe_level.log10(),
+            solid_angle_sr: m.solid_angle,
+            main_beam_solid_angle_sr: m.main_beam_solid_angle,
+            main_beam_efficiency: m.main_beam_efficiency(),
+            directivity_dbi: 10.0 * m.directivity().log10(),
+            effective_area_m2: m.effective_area(C / (freq_MHz * 1e6)),
+        }
+    }
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.freq_MHz,
+            self.frame,
+            self.peak_lon_deg,
+            self.peak_lat_deg,
+            self.fwhm_major_deg,
+            self.fwhm_minor_deg,
+            self.fwhm_pa_deg,
+            self.first_null_deg,
+            self.sidelobe_db,
+            self.solid_angle_sr,
+            self.main_beam_solid_angle_sr,
+            self.main_beam_efficiency,
+            self.directivity_dbi,
+            self.effective_area_m2
+        )
+    }
+}
+
+/// (frequency in MHz, power) of each channel, with the sample directions and
+/// solid angles
+type Samples = (Vec<(f64, Vec<f64>)>, Vec<Vec3d<f64>>, Vec<f64>, Frame);
+
+fn healpix_samples(fname: &str) -> Result<Samples> {
+    let (maps, freqs_MHz, coordsys) = read_healpix_cube(fname)?;
+    let frame = match coordsys {
+        Some(c) => Frame::Sky(
+            SkyFrame::from_coordsys(&c)
+                .ok_or_else(|| Error::Invalid(format!("{}: unsupported COORDSYS {}", fname, c)))?,
+        ),
+        None => Frame::AltAz,
+    };
+    let npix = maps[0].len();
+    let nside = npix2nside(npix);
+    let dirs = (0..npix)
+        .map(|ipix| Vec3d::from_sph_coord(pix2ang_ring::<f64>(nside, ipix)))
+        .collect();
+    let areas = vec![4.0 * PI / npix as f64; npix];
+    Ok((
+        freqs_MHz.into_iter().zip(maps).collect(),
+        dirs,
+        areas,
+        frame,
+    ))
+}
+
+/// shape and data of the image cube in the primary hdu
+fn read_cube(fname: &str) -> Result<(Vec<usize>, Vec<f64>)> {
+    let mut fitsfile = open_fits(fname)?;
+    let hdu = fitsfile.primary_hdu().with_path(fname)?;
+    let shape = match &hdu.info {
+        HduInfo::ImageInfo { shape, .. } if shape.len() == 3 => shape.clone(),
+        _ => {
+            return Err(Error::Invalid(format!(
+                "{}: no image cube in the primary hdu",
+                fname
+            )))
+        }
+    };
+    let data = hdu.read_image(&mut fitsfile).with_path(fname)?;
+    Ok((shape, data))
+}
+
+/// power of the x feed, |xx|^2 + |xy|^2, from the Jones patches written by
+/// `array-beam-patch` with the out prefix `prefix`
+fn patch_samples(prefix: &str) -> Result<Samples> {
+    let fname = &format!("{}_xx_re.fits", prefix);
+    let (shape, _) = read_cube(fname)?;
+    let (nfreq, ny, nx) = (shape[0], shape[1], shape[2]);
+    let mut data = vec![0.0; nfreq * ny * nx];
+    for part in ["xx_re", "xx_im", "xy_re", "xy_im"] {
+        let name = format!("{}_{}.fits", prefix, part);
+        let (s, d) = read_cube(&name)?;
+        if s != shape {
+            return Err(Error::Invalid(format!(
+                "{}: shape {:?} instead of {:?} as in {}",
+                name, s, shape, fname
+            )));
+        }
+        data.iter_mut().zip(d).for_each(|(p, x)| *p += x * x);
+    }
+    let mut fitsfile = open_fits(fname)?;
+    let hdu = fitsfile.primary_hdu().with_path(fname)?;
+    let ctype1: String = read_key(&mut fitsfile, &hdu, "CTYPE1", fname)?;
+    let (patch_frame, proj) = PatchFrame::from_ctype(&ctype1)
+        .ok_or_else(|| Error::Invalid(format!("{}: unsupported CTYPE1 {}", fname, ctype1)))?;
+    let mut key = |k: &str| read_key::<f64>(&mut fitsfile, &hdu, k, fname);
+    let (crpix1, cdelt1) = (key("CRPIX1")?, key("CDELT1")?);
+    let (crpix2, cdelt2) = (key("CRPIX2")?, key("CDELT2")?);
+    let (crval1, crval2) = (key("CRVAL1")?, key("CRVAL2")?);
+    let (crval3, cdelt3) = (key("CRVAL3")?, key("CDELT3")?);
+
+    let (tangent_frame, frame) = match patch_frame {
+        PatchFrame::AltAz => (
+            TangentFrame::from_altaz(&AltAz::new(crval2.to_radians(), crval1.to_radians())),
+            Frame::AltAz,
+        ),
+        PatchFrame::Sky(f) => (
+            TangentFrame::from_lon_lat(crval1.to_radians(), crval2.to_radians()),
+            Frame::Sky(f),
+        ),
+    };
+    let (da, db) = (cdelt1.to_radians(), cdelt2.to_radians());
+    let dir = |a: f64, b: f64| proj.deproject(a, b).map(|c| tangent_frame.to_vec3d(&c));
+    let mut pixels = Vec::new();
+    let mut dirs = Vec::new();
+    let mut areas = Vec::new();
+    for iy in 0..ny {
+        let b = (iy as f64 + 1.0 - crpix2) * db;
+        for ix in 0..nx {
+            let a = (ix as f64 + 1.0 - crpix1) * da;
+            // the solid angle from the jacobian of the deprojection
+            let h = 1e-3;
+            if let (Some(v), Some(va), Some(vb)) =
+                (dir(a, b), dir(a + h * da, b), dir(a, b + h * db))
+            {
+                let (ea, eb) = ((va + v * -1.0) * (1.0 / h), (vb + v * -1.0) * (1.0 / h));
+                let cross = Vec3d {
+                    x: ea.y * eb.z - ea.z * eb.y,
+                    y: ea.z * eb.x - ea.x * eb.z,
+                    z: ea.x * eb.y - ea.y * eb.x,
+                };
+                pixels.push(iy * nx + ix);
+                dirs.push(v);
+                areas.push(cross.dot(cross).sqrt());
+            }
+        }
+    }
+    let channels = (0..nfreq)
+        .map(|i| {
+            let plane = &data[i * nx * ny..(i + 1) * nx * ny];
+            (
+                (crval3 + i as f64 * cdelt3) / 1e6,
+                pixels.iter().map(|&p| plane[p]).collect(),
+            )
+        })
+        .collect();
+    Ok((channels, dirs, areas, frame))
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let (channels, dirs, areas, frame) = if let Some(f) = &args.healpix {
+        healpix_samples(f)?
+    } else {
+        patch_samples(args.patch.as_ref().unwrap())?
+    };
+
+    let reports: Vec<_> = channels
+        .into_iter()
+        .map(|(freq_MHz, power)| {
+            calc_beam_metrics(&dirs, &power, &areas)
+                .map(|m| Report::new(&m, freq_MHz, frame))
+                .map_err(|e| Error::Invalid(format!("{} MHz: {}", freq_MHz, e)))
+        })
+        .collect::<Result<_>>()?;
+
+    let out_name = args.outfile.as_deref().unwrap_or("stdout");
+    let mut out: Box<dyn Write> = match &args.outfile {
+        Some(f) => Box::new(File::create(f).with_path(f)?),
+        None => Box::new(std::io::stdout()),
+    };
+    match args.format {
+        Format::Yaml => serde_yaml::to_writer(&mut out, &reports).with_path(out_name)?,
+        Format::Csv => {
+            writeln!(out, "{}", CSV_HEADER).with_path(out_name)?;
+            for r in &reports {
+                writeln!(out, "{}", r.csv_row()).with_path(out_name)?;
+            }
+        }
+    }
+    Ok(())
+}
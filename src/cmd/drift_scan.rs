@@ -0,0 +1,144 @@
+use std::fs::remove_file;
+
+use fitsio::{
+    images::{ImageDescription, ImageType},
+    FitsFile,
+};
+
+use jm21cma::{
+    beam::Beam,
+    error::{Result, WithPath},
+    parallel::par_map,
+};
+
+use ndarray::{s, Array1, Array3};
+
+use scorus::healpix::utils::nside2npix;
+
+use super::{
+    sky_dirs, station_dirs, Array, ArrayArgs, FreqArgs, ScanArgs, SkyArgs, TimeArgs, TrackingArgs,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    array: ArrayArgs,
+
+    #[clap(flatten)]
+    tracking: TrackingArgs,
+
+    #[clap(flatten)]
+    freqs: FreqArgs,
+
+    #[clap(flatten)]
+    sky: SkyArgs,
+
+    #[clap(flatten)]
+    time: TimeArgs,
+
+    #[clap(flatten)]
+    scan: ScanArgs,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out fits, a time x freq x healpix pixel cube"
+    )]
+    outfile: String,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let array = Array::new(&args.array)?;
+    let site = array.site()?;
+    let lat = site.lat_deg.to_radians();
+    let lst0 = args.time.lst(site)?;
+    let lsts = args.scan.lsts(lst0);
+
+    let cube = args.freqs.load()?;
+    let freqs_mhz = args.freqs.freqs_mhz(&cube)?;
+    let ant_beams: Vec<_> = freqs_mhz
+        .iter()
+        .map(|&f| cube.power_at_freq(f, args.freqs.freq_interp))
+        .collect::<Result<_>>()?;
+    // steered towards the phase center of each time step in turn
+    let mut beams: Vec<_> = freqs_mhz
+        .iter()
+        .zip(ant_beams.iter())
+        .map(|(&f, ant_beam)| {
+            array.beam(
+                ant_beam,
+                f,
+                args.freqs.freq_interp,
+                &args.tracking.altaz(lst0, lat),
+            )
+        })
+        .collect::<Result<_>>()?;
+
+    let nside = args.sky.nside;
+    let npix = nside2npix(nside);
+    let sky_dirs = sky_dirs(nside, args.sky.frame);
+
+    let mut beam_cube = Array3::<f64>::zeros((lsts.len(), freqs_mhz.len(), npix));
+    for (t_idx, &lst) in lsts.iter().enumerate() {
+        let ptg = args.tracking.altaz(lst, lat);
+        eprintln!(
+            "lst={} h, phase center alt={} az={} deg",
+            lst.to_degrees() / 15.0,
+            ptg.alt.to_degrees(),
+            ptg.az.to_degrees()
+        );
+        let dirs = station_dirs(&sky_dirs, lst, lat);
+        beams.iter_mut().for_each(|b| b.pointing = ptg);
+        // the frequencies in parallel, and the pixels of each
+        let maps = par_map(0..beams.len(), |f_idx| {
+            beams[f_idx].power_many(&dirs, freqs_mhz[f_idx] * 1e6)
+        });
+        for (f_idx, beam) in maps.into_iter().enumerate() {
+            beam_cube
+                .slice_mut(s![t_idx, f_idx, ..])
+                .assign(&Array1::from(beam));
+        }
+    }
+
+    let image_description = ImageDescription {
+        data_type: ImageType::Double,
+        dimensions: &[lsts.len(), freqs_mhz.len(), npix],
+    };
+    let _ = remove_file(&args.outfile);
+    let mut output_fits = FitsFile::create(&args.outfile)
+        .with_custom_primary(&image_description)
+        .open()
+        .with_path(&args.outfile)?;
+    let hdu = output_fits.primary_hdu().with_path(&args.outfile)?;
+    hdu.write_image(&mut output_fits, beam_cube.as_slice().unwrap())
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "PIXTYPE", "HEALPIX")
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "ORDERING", "RING")
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "NSIDE", nside as i64)
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "COORDSYS", args.sky.frame.coordsys())
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CTYPE3", "TIME")
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CRPIX3", 1)
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CRVAL3", 0.0)
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CDELT3", args.scan.cadence)
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "CUNIT3", "s")
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut output_fits, "LST0", lst0.to_degrees() / 15.0)
+        .with_path(&args.outfile)?;
+    if let Some(utc) = &args.time.utc {
+        hdu.write_key(&mut output_fits, "DATE-OBS", utc.as_str())
+            .with_path(&args.outfile)?;
+    }
+    for (i, &freq_mhz) in freqs_mhz.iter().enumerate() {
+        hdu.write_key(&mut output_fits, &format!("FREQ{}", i + 1), freq_mhz * 1e6)
+            .with_path(&args.outfile)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::BTreeMap,
+    env::current_dir,
+    fs::{canonicalize, read_to_string, File},
+};
+
+use jm21cma::{
+    cfg::{ArrayCfg, ElementCfg},
+    embedded::{assign_embedded, cluster_nec_deck, set_elements},
+    error::{Error, Result, WithPath},
+    single_ant_model::SingleAntPol,
+};
+
+use super::ant_beam::write_nec_beam;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(short = 'n', long = "nec", value_name = "nec file of a single element")]
+    nec: String,
+
+    #[clap(
+        long = "cluster",
+        value_name = "array cfg of the simulated cluster, e.g. from the layout subcommand"
+    )]
+    cluster: String,
+
+    #[clap(
+        long = "drive",
+        num_args(1..),
+        value_name = "0-based indices of the cluster antennas whose patterns are simulated, all if not given"
+    )]
+    drive: Vec<usize>,
+
+    #[clap(short = 's', long = "nside", value_name = "nside")]
+    nside: usize,
+
+    #[clap(short = 'f', long = "freq", required = true, num_args(1..), value_name = "freqs in MHz")]
+    freq_mhz: Vec<f64>,
+
+    #[clap(
+        long = "load_ohm",
+        default_value = "50",
+        value_name = "load terminating the feeds of the other antennas in ohm"
+    )]
+    load_ohm: f64,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out prefix, writes <out>_<antenna>_<freq>MHz.fits"
+    )]
+    outfile: String,
+
+    #[clap(
+        short = 'c',
+        long = "cfg",
+        requires = "cfg_out",
+        value_name = "station array cfg whose antennas get the embedded patterns"
+    )]
+    cfg: Option<String>,
+
+    #[clap(long = "cfg_out", value_name = "out station array cfg")]
+    cfg_out: Option<String>,
+
+    #[clap(
+        long = "radius",
+        value_name = "distance in m within which neighbours are compared, 1.5 times the shortest cluster baseline if not given"
+    )]
+    radius: Option<f64>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let deck = read_to_string(&args.nec).with_path(&args.nec)?;
+    let cluster = ArrayCfg::from_yaml(&args.cluster)?;
+    let pos: Vec<_> = cluster
+        .all_ants()
+        .iter()
+        .map(|a| (a.pos.0, a.pos.1))
+        .collect();
+    let drive: Vec<_> = if args.drive.is_empty() {
+        (0..pos.len()).collect()
+    } else {
+        args.drive.clone()
+    };
+
+    let mut elements = BTreeMap::new();
+    for &i in &drive {
+        let cluster_deck = cluster_nec_deck(&deck, &pos, i, args.load_ohm)
+            .map_err(|e| Error::Invalid(format!("{}: {}", args.nec, e)))?;
+        let mut files = Vec::new();
+        for &freq in &args.freq_mhz {
+            let out = format!("{}_{}_{}MHz.fits", args.outfile, i, freq);
+            eprintln!("antenna {} of the cluster at {} MHz: {}", i, freq, out);
+            write_nec_beam(&cluster_deck, &args.nec, args.nside, freq, &out)?;
+            // without the E field, the elements would be combined with the
+            // phase and polarization of an idealized dipole, which loses the
+            // coupling the embedded patterns are for
+            if SingleAntPol::try_from_fits(&out)?.is_none() {
+                return Err(Error::Invalid(format!(
+                    "{}: no EFIELD extension, embedded element patterns need the complex far field, which ant-beam cannot export with this necrs",
+                    out
+                )));
+            }
+            // absolute, as the beam files of the cfg are relative to its directory
+            files.push(
+                canonicalize(&out)
+                    .with_path(&out)?
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        elements.insert(format!("embedded_{}", i), ElementCfg::Beam { files });
+    }
+
+    if let (Some(cfg_name), Some(out_name)) = (&args.cfg, &args.cfg_out) {
+        let mut cfg = ArrayCfg::from_yaml(cfg_name)?;
+        let shortest = pos
+            .iter()
+            .enumerate()
+            .flat_map(|(i, p)| {
+                pos[i + 1..]
+                    .iter()
+                    .map(move |q| (p.0 - q.0).hypot(p.1 - q.1))
+            })
+            .fold(f64::INFINITY, f64::min);
+        if !shortest.is_finite() {
+            return Err(Error::Invalid(format!(
+                "{}: the cluster needs two antennas",
+                args.cluster
+            )));
+        }
+        let radius = args.radius.unwrap_or(1.5 * shortest);
+        if radius <= 0.0 {
+            return Err(Error::Invalid("--radius must be positive".to_string()));
+        }
+        // offsets match within a quarter of the lattice spacing
+        let assigned = assign_embedded(&cfg, &cluster, &drive, radius, 0.25 * shortest);
+        let names: Vec<_> = assigned.iter().map(|i| format!("embedded_{}", i)).collect();
+        for &i in &drive {
+            eprintln!(
+                "embedded_{}: {} antennas",
+                i,
+                assigned.iter().filter(|&&a| a == i).count()
+            );
+        }
+        set_elements(&mut cfg, &names);
+        // the beam files of the station cfg are relative to its own directory
+        // once loaded, so they are made absolute for the cfg written elsewhere
+        let cwd = current_dir().with_path(".")?;
+        for e in cfg.elements.values_mut() {
+            if let ElementCfg::Beam { files } = e {
+                files
+                    .iter_mut()
+                    .for_each(|f| *f = cwd.join(&f).to_string_lossy().into_owned());
+            }
+        }
+        cfg.elements.extend(elements);
+        serde_yaml::to_writer(File::create(out_name).with_path(out_name)?, &cfg)
+            .with_path(out_name)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+#![allow(non_snake_case)]
+
+use std::{fs::File, io::Write};
+
+use jm21cma::{
+    constants::LIGHT_SPEED as C,
+    error::{Result, WithPath},
+    grating_lobes::find_grating_lobes,
+};
+
+use serde::Serialize;
+
+use super::{Array, ArrayArgs, PointingArgs};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    array: ArrayArgs,
+
+    #[clap(flatten)]
+    pointing: PointingArgs,
+
+    #[clap(short = 'f', long = "freq_min", value_name = "first channel in MHz")]
+    freq_min: f64,
+
+    #[clap(short = 'F', long = "freq_max", value_name = "last channel in MHz")]
+    freq_max: f64,
+
+    #[clap(short = 'n', long = "nfreq", value_name = "num of channels")]
+    nfreq: usize,
+
+    #[clap(
+        long = "threshold",
+        default_value = "-3",
+        allow_hyphen_values = true,
+        value_name = "lobe level relative to the main lobe in dB"
+    )]
+    threshold_db: f64,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out yaml, stdout if not given"
+    )]
+    outfile: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LobeReport {
+    /// negative for lobes beyond the horizon
+    alt_deg: f64,
+    az_deg: f64,
+    level_db: f64,
+}
+
+#[derive(Serialize)]
+struct FreqReport {
+    freq_MHz: f64,
+    /// whether any grating lobe is above the horizon
+    visible: bool,
+    lobes: Vec<LobeReport>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let array = Array::new(&args.array)?;
+    let (ant_x, ant_y, ant_z) = array.cfg.positions();
+    let ptg = args.pointing.altaz();
+    let threshold = 10f64.powf(args.threshold_db / 10.0);
+
+    let reports: Vec<_> = (0..args.nfreq)
+        .map(|i| {
+            let freq_MHz = if args.nfreq > 1 {
+                args.freq_min + (args.freq_max - args.freq_min) * i as f64 / (args.nfreq - 1) as f64
+            } else {
+                args.freq_min
+            };
+            let freq = freq_MHz * 1e6;
+            let lobes = find_grating_lobes(
+                &ant_x,
+                &ant_y,
+                &ant_z,
+                &array.w_list,
+                &array.phases(&ptg, freq),
+                C / freq,
+                &ptg,
+                threshold,
+            );
+            FreqReport {
+                freq_MHz,
+                visible: lobes.iter().any(|g| g.visible()),
+                lobes: lobes
+                    .iter()
+                    .map(|g| LobeReport {
+                        alt_deg: g.dir.alt.to_degrees(),
+                        az_deg: g.dir.az.to_degrees(),
+                        level_db: 10.0 * g.level.log10(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    if let Some(r) = reports.iter().find(|r| r.visible) {
+        eprintln!("grating lobes above the horizon from {} MHz", r.freq_MHz);
+    }
+
+    let out: Box<dyn Write> = match &args.outfile {
+        Some(f) => Box::new(File::create(f).with_path(f)?),
+        None => Box::new(std::io::stdout()),
+    };
+    serde_yaml::to_writer(out, &reports).with_path(args.outfile.as_deref().unwrap_or("stdout"))
+}
@@ -0,0 +1,92 @@
+use std::{fs::File, io::Write};
+
+use jm21cma::{
+    cfg::{ArrayCfg, LengthUnit, PosFrame, SiteCfg},
+    error::{Error, Result, WithPath},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(long = "csv", value_name = "antenna table with a header line")]
+    csv: String,
+
+    #[clap(
+        long = "frame",
+        default_value = "enu",
+        value_name = "enu (east,north,up)|ecef (x,y,z)|geodetic (lon_deg,lat_deg,height)"
+    )]
+    frame: PosFrame,
+
+    #[clap(long = "units", default_value = "m", value_name = "m|cm|mm")]
+    units: LengthUnit,
+
+    #[clap(
+        long = "ref_lon",
+        requires_all = ["ref_lat", "ref_height"],
+        allow_hyphen_values = true,
+        value_name = "reference point longitude in deg, the centroid of the antennas if not given"
+    )]
+    ref_lon: Option<f64>,
+
+    #[clap(
+        long = "ref_lat",
+        requires = "ref_lon",
+        allow_hyphen_values = true,
+        value_name = "reference point latitude in deg"
+    )]
+    ref_lat: Option<f64>,
+
+    #[clap(
+        long = "ref_height",
+        requires = "ref_lon",
+        allow_hyphen_values = true,
+        value_name = "reference point height above the WGS84 ellipsoid in m"
+    )]
+    ref_height: Option<f64>,
+
+    #[clap(
+        long = "keep_frame",
+        help = "write the positions as given, with their frame and units, instead of converting them to enu in m"
+    )]
+    keep_frame: bool,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out array cfg yaml, stdout if not given"
+    )]
+    outfile: Option<String>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let site = match (args.ref_lon, args.ref_lat, args.ref_height) {
+        (Some(lon_deg), Some(lat_deg), Some(height_m)) => Some(SiteCfg {
+            lon_deg,
+            lat_deg,
+            height_m,
+        }),
+        _ => None,
+    };
+    let mut cfg = ArrayCfg::from_csv(&args.csv, args.frame, args.units, site)?;
+    if cfg.site.is_none() {
+        cfg.site = cfg.centroid_site();
+    }
+    if let Some(s) = &cfg.site {
+        eprintln!(
+            "reference point: lon={} deg lat={} deg height={} m",
+            s.lon_deg, s.lat_deg, s.height_m
+        );
+    }
+    if !args.keep_frame {
+        cfg = cfg
+            .to_enu()
+            .map_err(|e| Error::Invalid(format!("{}: {}", args.csv, e)))?;
+    }
+    eprintln!("{} antennas", cfg.ants.len());
+
+    let out: Box<dyn Write> = match &args.outfile {
+        Some(f) => Box::new(File::create(f).with_path(f)?),
+        None => Box::new(std::io::stdout()),
+    };
+    serde_yaml::to_writer(out, &cfg).with_path(args.outfile.as_deref().unwrap_or("stdout"))
+}
@@ -0,0 +1,165 @@
+use fitsio::{
+    images::{ImageDescription, ImageType},
+    FitsFile,
+};
+
+use jm21cma::{
+    beam::Beam,
+    coordinates::AltAz,
+    error::{Result, WithPath},
+    parallel::par_map,
+};
+
+use ndarray::Array2;
+
+use std::fs::remove_file;
+
+use super::{default_element, Array, ArrayArgs, FreqArgs, PointingArgs};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    array: ArrayArgs,
+
+    #[clap(flatten)]
+    pointing: PointingArgs,
+
+    #[clap(flatten)]
+    freqs: FreqArgs,
+
+    #[clap(
+        short = 't',
+        long = "theta_min",
+        value_name = "zenith angle min in deg"
+    )]
+    theta_min: f64,
+
+    #[clap(
+        short = 'T',
+        long = "theta_max",
+        value_name = "zenith angle max in deg"
+    )]
+    theta_max: f64,
+
+    #[clap(short = 'n', long = "ntheta", value_name = "n theta")]
+    ntheta: usize,
+
+    #[clap(
+        short = 'p',
+        long = "phi_min",
+        allow_hyphen_values = true,
+        value_name = "az min in deg, north=0, east=90"
+    )]
+    phi_min: f64,
+
+    #[clap(
+        short = 'P',
+        long = "phi_max",
+        allow_hyphen_values = true,
+        value_name = "az max in deg"
+    )]
+    phi_max: f64,
+
+    #[clap(short = 'N', long = "nphi", value_name = "num of phi")]
+    nphi: usize,
+
+    #[clap(short = 'o', long = "out", value_name = "outfits")]
+    outfile: String,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let array = Array::new(&args.array)?;
+    let ptg = args.pointing.altaz();
+
+    let dtheta = (args.theta_max - args.theta_min) / (args.ntheta - 1) as f64;
+    let dphi = (args.phi_max - args.phi_min) / (args.nphi - 1) as f64;
+
+    let mut buf = Array2::<f64>::zeros((args.ntheta * args.nphi, 10));
+
+    let image_description = ImageDescription {
+        data_type: ImageType::Double,
+        dimensions: &[args.ntheta * args.nphi, 10],
+    };
+    let _ = remove_file(&args.outfile);
+
+    let mut output_fits = FitsFile::create(&args.outfile)
+        .with_custom_primary(&image_description)
+        .open()
+        .with_path(&args.outfile)?;
+
+    let cube = args.freqs.load()?;
+    let freqs_mhz = args.freqs.freqs_mhz(&cube)?;
+    eprintln!("freq={:?} MHz", freqs_mhz);
+
+    let dirs: Vec<_> = (0..args.ntheta * args.nphi)
+        .map(|row_idx| {
+            let (phi_idx, theta_idx) = (row_idx / args.ntheta, row_idx % args.ntheta);
+            let phi = args.phi_min + phi_idx as f64 * dphi;
+            let theta = args.theta_min + theta_idx as f64 * dtheta;
+            (theta, phi)
+        })
+        .collect();
+    let altaz: Vec<_> = dirs
+        .iter()
+        .map(|&(theta, phi)| Some(AltAz::from_zenith_az(theta.to_radians(), phi.to_radians())))
+        .collect();
+
+    // the frequencies in parallel, and the directions of each
+    let tables = par_map(0..freqs_mhz.len(), |f_idx| -> Result<Vec<[f64; 10]>> {
+        let freq_mhz = freqs_mhz[f_idx];
+        let ant_beam = cube.power_at_freq(freq_mhz, args.freqs.freq_interp)?;
+        // fall back to the idealized polarization if the beam files hold no E field
+        let pol_beam = cube.pol_at_freq(freq_mhz, args.freqs.freq_interp)?;
+        let beam = array.beam(
+            default_element(&ant_beam, pol_beam.as_ref()),
+            freq_mhz,
+            args.freqs.freq_interp,
+            &ptg,
+        )?;
+        let jones = beam.jones_many(&altaz, freq_mhz * 1e6);
+        Ok(dirs
+            .iter()
+            .zip(jones.iter())
+            .map(|(&(theta, phi), jones)| {
+                let mut row = [0.0; 10];
+                row[0] = theta;
+                row[1] = phi;
+                row.chunks_exact_mut(2)
+                    .skip(1)
+                    .zip(jones.unwrap_or_default().iter())
+                    .for_each(|(a, &g)| {
+                        a[0] = g.re;
+                        a[1] = g.im;
+                    });
+                row
+            })
+            .collect())
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+
+    for (f_idx, rows) in tables.iter().enumerate() {
+        for (row_idx, row) in rows.iter().enumerate() {
+            buf.row_mut(row_idx)
+                .as_slice_mut()
+                .unwrap()
+                .copy_from_slice(row);
+        }
+        let image_description = ImageDescription {
+            data_type: ImageType::Double,
+            dimensions: &[args.ntheta * args.nphi, 10],
+        };
+        let hdu = if f_idx == 0 {
+            output_fits.primary_hdu().with_path(&args.outfile)?
+        } else {
+            output_fits
+                .create_image(format!("freq{}", f_idx), &image_description)
+                .with_path(&args.outfile)?
+        };
+        hdu.write_key(&mut output_fits, "FREQ", freqs_mhz[f_idx] * 1e6)
+            .with_path(&args.outfile)?;
+        hdu.write_image(&mut output_fits, buf.as_slice().unwrap())
+            .with_path(&args.outfile)?;
+    }
+    Ok(())
+}
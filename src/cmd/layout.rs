@@ -0,0 +1,94 @@
+use std::{fs::File, io::Write};
+
+use jm21cma::{
+    cfg::ArrayCfg,
+    error::{Result, WithPath},
+    layout::{layout_cfg, transform, Layout},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(
+        short = 'l',
+        long = "layout",
+        value_name = "hex:<spacing>:<rings>|square:<spacing>:<n>|ring:<radius>:<n>|random:<radius>:<n>:<min spacing>[:<seed>]"
+    )]
+    layout: Layout,
+
+    #[clap(
+        long = "pod",
+        value_name = "layout of the antennas of a group, one group at each position of --layout"
+    )]
+    pod: Option<Layout>,
+
+    #[clap(
+        long = "rotation",
+        default_value = "0",
+        allow_hyphen_values = true,
+        value_name = "rotation in deg, north towards east"
+    )]
+    rotation_deg: f64,
+
+    #[clap(
+        long = "pod_rotation",
+        default_value = "0",
+        allow_hyphen_values = true,
+        value_name = "rotation of the pods about their centres in deg"
+    )]
+    pod_rotation_deg: f64,
+
+    #[clap(
+        long = "east",
+        default_value = "0",
+        allow_hyphen_values = true,
+        value_name = "offset towards east in m"
+    )]
+    east: f64,
+
+    #[clap(
+        long = "north",
+        default_value = "0",
+        allow_hyphen_values = true,
+        value_name = "offset towards north in m"
+    )]
+    north: f64,
+
+    #[clap(long = "site", value_name = "array cfg whose site location is copied")]
+    site: Option<String>,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out array cfg yaml, stdout if not given"
+    )]
+    outfile: Option<String>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let pos = transform(
+        &args.layout.positions()?,
+        args.rotation_deg.to_radians(),
+        (args.east, args.north),
+    );
+    let pod = args
+        .pod
+        .map(|p| -> Result<_> {
+            Ok(transform(
+                &p.positions()?,
+                args.pod_rotation_deg.to_radians(),
+                (0.0, 0.0),
+            ))
+        })
+        .transpose()?;
+    let mut cfg = layout_cfg(&pos, pod.as_deref());
+    if let Some(f) = &args.site {
+        cfg.site = ArrayCfg::from_yaml(f)?.site;
+    }
+    eprintln!("{} antennas", cfg.all_ants().len());
+
+    let out: Box<dyn Write> = match &args.outfile {
+        Some(f) => Box::new(File::create(f).with_path(f)?),
+        None => Box::new(std::io::stdout()),
+    };
+    serde_yaml::to_writer(out, &cfg).with_path(args.outfile.as_deref().unwrap_or("stdout"))
+}
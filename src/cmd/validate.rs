@@ -0,0 +1,119 @@
+use std::{fs::remove_file, fs::File, io::Write};
+
+use fitsio::{
+    images::{ImageDescription, ImageType},
+    FitsFile,
+};
+
+use jm21cma::{
+    cfg::ArrayCfg,
+    error::{Error, Result, WithPath},
+    validation::{uv_grid, validate},
+};
+
+/// largest side of the uv grid written by --uv_out
+const MAX_UV_GRID: usize = 8192;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(short = 'c', long = "cfg", value_name = "array_cfg.yaml")]
+    cfg: String,
+
+    #[clap(
+        long = "element_size",
+        default_value = "1",
+        value_name = "smallest distance between antennas and uv cell in m"
+    )]
+    element_size: f64,
+
+    #[clap(long = "flat", help = "antennas off the z = 0 plane are errors")]
+    flat: bool,
+
+    #[clap(
+        long = "uv_out",
+        value_name = "out fits, number of baselines per uv cell of a zenith snapshot"
+    )]
+    uv_out: Option<String>,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out yaml, stdout if not given"
+    )]
+    outfile: Option<String>,
+}
+
+fn write_uv_fits(fname: &str, grid: &[f64], n: usize, cell: f64) -> Result<()> {
+    let image_description = ImageDescription {
+        data_type: ImageType::Double,
+        dimensions: &[n, n],
+    };
+    let _ = remove_file(fname);
+    let mut fitsfile = FitsFile::create(fname)
+        .with_custom_primary(&image_description)
+        .open()
+        .with_path(fname)?;
+    let hdu = fitsfile.primary_hdu().with_path(fname)?;
+    hdu.write_image(&mut fitsfile, grid).with_path(fname)?;
+    for (i, ctype) in [(1, "U"), (2, "V")] {
+        hdu.write_key(&mut fitsfile, &format!("CTYPE{}", i), ctype)
+            .with_path(fname)?;
+        hdu.write_key(&mut fitsfile, &format!("CRPIX{}", i), (n / 2 + 1) as f64)
+            .with_path(fname)?;
+        hdu.write_key(&mut fitsfile, &format!("CDELT{}", i), cell)
+            .with_path(fname)?;
+        hdu.write_key(&mut fitsfile, &format!("CRVAL{}", i), 0.0)
+            .with_path(fname)?;
+        hdu.write_key(&mut fitsfile, &format!("CUNIT{}", i), "m")
+            .with_path(fname)?;
+    }
+    Ok(())
+}
+
+pub fn run(args: Args) -> Result<()> {
+    if args.element_size <= 0.0 {
+        return Err(Error::Invalid(
+            "--element_size must be positive".to_string(),
+        ));
+    }
+    // not ArrayCfg::from_yaml, which fails on the hard errors before the report
+    let cfg = ArrayCfg::from_yaml_unchecked(&args.cfg)?;
+    let diag = validate(&cfg, args.element_size, args.flat);
+
+    if let Some(f) = &args.uv_out {
+        let n = 2 * (diag.max_baseline_m / args.element_size).ceil() as usize + 3;
+        if n > MAX_UV_GRID {
+            return Err(Error::Invalid(format!(
+                "--uv_out would need {} x {} cells, increase --element_size",
+                n, n
+            )));
+        }
+        write_uv_fits(
+            f,
+            &uv_grid(&cfg, args.element_size, n),
+            n,
+            args.element_size,
+        )?;
+    }
+
+    let out_name = args.outfile.as_deref().unwrap_or("stdout");
+    let mut out: Box<dyn Write> = match &args.outfile {
+        Some(f) => Box::new(File::create(f).with_path(f)?),
+        None => Box::new(std::io::stdout()),
+    };
+    serde_yaml::to_writer(&mut out, &diag).with_path(out_name)?;
+
+    for w in &diag.warnings {
+        eprintln!("warning: {}", w);
+    }
+    if diag.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Invalid(format!(
+            "{}: {} errors in the cfg, first: {}",
+            args.cfg,
+            diag.errors.len(),
+            diag.errors[0]
+        )))
+    }
+}
@@ -0,0 +1,271 @@
+use std::{fs::remove_file, path::Path};
+
+use fitsio::{
+    tables::{ColumnDataType, ColumnDescription},
+    FitsFile,
+};
+
+use jm21cma::{
+    beam::Beam,
+    cfg::{load_yaml, ArrayCfg, InterferometerCfg},
+    constants::LIGHT_SPEED as C,
+    coordinates::AltAz,
+    error::{Result, WithPath},
+    sky::eq_to_enu,
+    sky_model::{pixel_flux_jy, PointSource, SkyCube},
+    visibility::{calc_uvw, calc_visibility, Jones, SkyComponent},
+};
+
+use scorus::coordinates::Vec3d;
+
+use super::{
+    default_element, sky_dirs, station_dirs, Array, FreqArgs, ScanArgs, SteeringArgs, TimeArgs,
+    TrackingArgs,
+};
+
+/// names of the visibility columns, the real and imaginary parts of xx, xy, yx and yy
+const VIS_COLUMNS: [&str; 8] = [
+    "XX_RE", "XX_IM", "XY_RE", "XY_IM", "YX_RE", "YX_IM", "YY_RE", "YY_IM",
+];
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[clap(short = 'c', long = "cfg", value_name = "interferometer_cfg.yaml")]
+    cfg: String,
+
+    #[clap(flatten)]
+    steering: SteeringArgs,
+
+    #[clap(flatten)]
+    tracking: TrackingArgs,
+
+    #[clap(flatten)]
+    freqs: FreqArgs,
+
+    #[clap(
+        long = "sources",
+        required_unless_present = "diffuse",
+        value_name = "point sources yaml"
+    )]
+    sources: Option<String>,
+
+    #[clap(
+        long = "diffuse",
+        value_name = "diffuse sky temperature in healpix, one column per frequency"
+    )]
+    diffuse: Option<String>,
+
+    #[clap(flatten)]
+    time: TimeArgs,
+
+    #[clap(flatten)]
+    scan: ScanArgs,
+
+    #[clap(
+        short = 'o',
+        long = "out",
+        value_name = "out fits, visibilities in Jy in a binary table"
+    )]
+    outfile: String,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let cfg = InterferometerCfg::from_yaml(&args.cfg)?;
+    let cfg_dir = Path::new(&args.cfg).parent().unwrap_or(Path::new(""));
+    let stations: Vec<_> = cfg
+        .stations
+        .iter()
+        .map(|st| {
+            let array_cfg = ArrayCfg::from_yaml(&cfg_dir.join(&st.array).to_string_lossy())?;
+            let (x, y, z) = st.pos;
+            Ok((
+                Vec3d { x, y, z },
+                Array::from_cfg(array_cfg, &args.steering)?,
+            ))
+        })
+        .collect::<Result<_>>()?;
+
+    let lat = cfg.site.lat_deg.to_radians();
+    let lst0 = args.time.lst(&cfg.site)?;
+    let lsts = args.scan.lsts(lst0);
+
+    let cube = args.freqs.load()?;
+    let freqs_mhz = args.freqs.freqs_mhz(&cube)?;
+
+    let sources: Vec<PointSource> = match &args.sources {
+        Some(f) => load_yaml(f)?,
+        None => Vec::new(),
+    };
+    let diffuse = args
+        .diffuse
+        .as_ref()
+        .map(|f| SkyCube::from_fits(f))
+        .transpose()?;
+    let diffuse_dirs = diffuse.as_ref().map(|d| sky_dirs(d.nside, d.frame));
+
+    let mut lst_col = Vec::new();
+    let mut freq_col = Vec::new();
+    let mut ant1_col = Vec::new();
+    let mut ant2_col = Vec::new();
+    let mut uvw_cols = vec![Vec::new(); 3];
+    let mut vis_cols = vec![Vec::new(); VIS_COLUMNS.len()];
+
+    for &lst in &lsts {
+        let ptg = args.tracking.altaz(lst, lat);
+        let phase_center = ptg.to_vec3d();
+        eprintln!("lst={} h", lst.to_degrees() / 15.0);
+
+        // sky components above the horizon, as (direction, index of the
+        // source or pixel)
+        let src_dirs: Vec<_> = sources
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (eq_to_enu(&s.dir(), lst, lat), i))
+            .filter(|(v, _)| v.z > 0.0)
+            .collect();
+        let pix_dirs: Vec<_> = diffuse_dirs
+            .as_ref()
+            .map(|d| {
+                station_dirs(d, lst, lat)
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(i, d)| d.map(|d| (d.to_vec3d(), i)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let dirs: Vec<_> = src_dirs
+            .iter()
+            .chain(pix_dirs.iter())
+            .map(|(v, _)| *v)
+            .collect();
+
+        for &freq_mhz in &freqs_mhz {
+            let freq = freq_mhz * 1e6;
+            let ant_beam = cube.power_at_freq(freq_mhz, args.freqs.freq_interp)?;
+            let pol_beam = cube.pol_at_freq(freq_mhz, args.freqs.freq_interp)?;
+
+            let mut comps: Vec<_> = src_dirs
+                .iter()
+                .map(|&(dir, i)| SkyComponent {
+                    dir,
+                    flux: sources[i].flux_at(freq_mhz),
+                })
+                .collect();
+            if let Some(d) = &diffuse {
+                let t = d.map_at_freq(freq_mhz, args.freqs.freq_interp)?;
+                comps.extend(pix_dirs.iter().map(|&(dir, i)| SkyComponent {
+                    dir,
+                    flux: pixel_flux_jy(t[i], freq_mhz, d.nside),
+                }));
+            }
+
+            let some_dirs: Vec<_> = dirs.iter().map(|v| Some(AltAz::from_vec3d(v))).collect();
+            let station_jones: Vec<Vec<Jones>> = stations
+                .iter()
+                .map(|(_, array)| {
+                    Ok(array
+                        .beam(
+                            default_element(&ant_beam, pol_beam.as_ref()),
+                            freq_mhz,
+                            args.freqs.freq_interp,
+                            &ptg,
+                        )?
+                        .jones_many(&some_dirs, freq)
+                        .into_iter()
+                        .flatten()
+                        .collect())
+                })
+                .collect::<Result<_>>()?;
+
+            for i in 0..stations.len() {
+                for j in i + 1..stations.len() {
+                    let (pos_i, pos_j) = (&stations[i].0, &stations[j].0);
+                    let vis = calc_visibility(
+                        &comps,
+                        &station_jones[i],
+                        &station_jones[j],
+                        pos_i,
+                        pos_j,
+                        &phase_center,
+                        C / freq,
+                    );
+                    let b = Vec3d {
+                        x: pos_i.x - pos_j.x,
+                        y: pos_i.y - pos_j.y,
+                        z: pos_i.z - pos_j.z,
+                    };
+                    let (u, v, w) = calc_uvw(&b, &phase_center, lat);
+                    lst_col.push(lst.to_degrees() / 15.0);
+                    freq_col.push(freq);
+                    ant1_col.push(i as i32 + 1);
+                    ant2_col.push(j as i32 + 1);
+                    uvw_cols[0].push(u);
+                    uvw_cols[1].push(v);
+                    uvw_cols[2].push(w);
+                    for (k, x) in vis.iter().enumerate() {
+                        vis_cols[2 * k].push(x.re);
+                        vis_cols[2 * k + 1].push(x.im);
+                    }
+                }
+            }
+        }
+    }
+
+    let double_cols = ["LST", "FREQ", "U", "V", "W"]
+        .into_iter()
+        .chain(VIS_COLUMNS.iter().cloned());
+    let column_descriptions: Vec<_> = ["ANT1", "ANT2"]
+        .iter()
+        .map(|&name| {
+            ColumnDescription::new(name)
+                .with_type(ColumnDataType::Int)
+                .create()
+                .with_path(&args.outfile)
+        })
+        .chain(double_cols.map(|name| {
+            ColumnDescription::new(name)
+                .with_type(ColumnDataType::Double)
+                .create()
+                .with_path(&args.outfile)
+        }))
+        .collect::<Result<_>>()?;
+
+    let _ = remove_file(&args.outfile);
+    let mut fitsfile = FitsFile::create(&args.outfile)
+        .open()
+        .with_path(&args.outfile)?;
+    let hdu = fitsfile
+        .create_table("VISIBILITY", &column_descriptions)
+        .with_path(&args.outfile)?;
+    hdu.write_col(&mut fitsfile, "ANT1", &ant1_col)
+        .with_path(&args.outfile)?;
+    hdu.write_col(&mut fitsfile, "ANT2", &ant2_col)
+        .with_path(&args.outfile)?;
+    hdu.write_col(&mut fitsfile, "LST", &lst_col)
+        .with_path(&args.outfile)?;
+    hdu.write_col(&mut fitsfile, "FREQ", &freq_col)
+        .with_path(&args.outfile)?;
+    for (&name, col) in ["U", "V", "W"].iter().zip(uvw_cols.iter()) {
+        hdu.write_col(&mut fitsfile, name, col)
+            .with_path(&args.outfile)?;
+    }
+    for (&name, col) in VIS_COLUMNS.iter().zip(vis_cols.iter()) {
+        hdu.write_col(&mut fitsfile, name, col)
+            .with_path(&args.outfile)?;
+    }
+    hdu.write_key(&mut fitsfile, "BUNIT", "Jy")
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut fitsfile, "SITELON", cfg.site.lon_deg)
+        .with_path(&args.outfile)?;
+    hdu.write_key(&mut fitsfile, "SITELAT", cfg.site.lat_deg)
+        .with_path(&args.outfile)?;
+    if let Some(utc) = &args.time.utc {
+        hdu.write_key(&mut fitsfile, "DATE-OBS", utc.as_str())
+            .with_path(&args.outfile)?;
+    }
+    for (i, st) in cfg.stations.iter().enumerate() {
+        hdu.write_key(&mut fitsfile, &format!("STN{}", i + 1), st.name.as_str())
+            .with_path(&args.outfile)?;
+    }
+    Ok(())
+}
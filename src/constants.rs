@@ -1 +1,3 @@
 pub const LIGHT_SPEED: f64 = 2.99792458E8;
+/// ratio of the mean solar day to the sidereal day
+pub const SIDEREAL_RATE: f64 = 1.002737811911354;
@@ -0,0 +1,57 @@
+//! Directions in the local horizontal frame of the station.
+//!
+//! The station frame has x pointing to the east, y to the north and z to the
+//! zenith; antenna positions in `ArrayCfg` are given in this frame.
+//! Azimuths are measured from the north through the east.
+//! HEALPix maps in the local frame (element beams, full-sky array beams) use
+//! the usual spherical coordinates of this frame, i.e., `pol` is the zenith
+//! angle and `az` is measured from the east (x) towards the north (y).
+
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use scorus::coordinates::{SphCoord, Vec3d};
+
+/// altitude and azimuth (east of north), in rad
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AltAz {
+    pub alt: f64,
+    pub az: f64,
+}
+
+impl AltAz {
+    pub fn new(alt: f64, az: f64) -> Self {
+        Self { alt, az }
+    }
+
+    pub fn from_zenith_az(zenith: f64, az: f64) -> Self {
+        Self::new(FRAC_PI_2 - zenith, az)
+    }
+
+    pub fn zenith(&self) -> f64 {
+        FRAC_PI_2 - self.alt
+    }
+
+    pub fn to_vec3d(&self) -> Vec3d<f64> {
+        Vec3d {
+            x: self.alt.cos() * self.az.sin(),
+            y: self.alt.cos() * self.az.cos(),
+            z: self.alt.sin(),
+        }
+    }
+
+    pub fn from_vec3d(v: &Vec3d<f64>) -> Self {
+        let alt = v.z.atan2(v.x.hypot(v.y));
+        let az = v.x.atan2(v.y).rem_euclid(2.0 * PI);
+        Self::new(alt, az)
+    }
+
+    /// spherical coordinates in the station frame, to look up HEALPix maps
+    /// and element patterns
+    pub fn to_sph_coord(&self) -> SphCoord<f64> {
+        SphCoord::new(self.zenith(), (FRAC_PI_2 - self.az).rem_euclid(2.0 * PI))
+    }
+
+    pub fn from_sph_coord(sph: &SphCoord<f64>) -> Self {
+        Self::from_zenith_az(sph.pol, (FRAC_PI_2 - sph.az).rem_euclid(2.0 * PI))
+    }
+}
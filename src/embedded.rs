@@ -0,0 +1,357 @@
+//! Embedded element patterns: NEC decks of a cluster of elements with one of
+//! them driven and the others terminated, and the assignment of the
+//! patterns to the antennas of a station by the arrangement of their nearest
+//! neighbours (e.g. centre or edge, and which edge).
+
+use crate::{
+    cfg::{AntCfg, ArrayCfg, GroupCfg},
+    error::{Error, Result},
+};
+
+fn fields(line: &str) -> Vec<&str> {
+    line.split_whitespace().collect()
+}
+
+/// the fields joined with the separator of the original card
+fn card(line: &str, fields: &[String]) -> String {
+    fields.join(if line.contains('\t') { "\t" } else { " " })
+}
+
+/// the card with the tags in `tag_fields` shifted by `shift`
+fn retag(line: &str, tag_fields: &[usize], shift: i64) -> Result<String> {
+    let f = fields(line);
+    let mut g: Vec<_> = f.iter().map(|s| s.to_string()).collect();
+    for &i in tag_fields {
+        if let Some(t) = f.get(i) {
+            g[i] = (parse_tag(t, line)? + shift).to_string();
+        }
+    }
+    Ok(card(line, &g))
+}
+
+fn parse_tag(s: &str, line: &str) -> Result<i64> {
+    s.parse::<i64>()
+        .map_err(|_| Error::Invalid(format!("invalid tag in NEC card: {}", line)))
+}
+
+/// NEC deck of copies of the element of `element_deck` at the horizontal
+/// positions `pos` (in m), shifted so that the element `driven` is at the
+/// origin, which is the phase reference of the pattern.
+/// The wires (`GW`) of the element are copied with their tags offset for
+/// each copy, the transmission lines (`TL`) likewise; the excitation (`EX`)
+/// is kept for the driven copy and replaced by a load of `load_ohm` (`LD`)
+/// at the feed of the others. The element deck must describe its wires with
+/// `GW` cards only, with tags from 1, all of them before any `GM` or `GS`.
+pub fn cluster_nec_deck(
+    element_deck: &str,
+    pos: &[(f64, f64)],
+    driven: usize,
+    load_ohm: f64,
+) -> Result<String> {
+    let lines: Vec<_> = element_deck
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .collect();
+    let ntags = lines
+        .iter()
+        .map(|l| fields(l))
+        .filter(|f| f[0] == "GW")
+        .map(|f| match f.get(1) {
+            Some(t) => parse_tag(t, &f.join(" ")),
+            None => Err(Error::Invalid("GW card without a tag".to_string())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if ntags.iter().any(|&t| t < 1) {
+        return Err(Error::Invalid(
+            "the wires of the element need tags from 1".to_string(),
+        ));
+    }
+    let ntags = ntags
+        .into_iter()
+        .max()
+        .ok_or_else(|| Error::Invalid("no GW card in the element deck".to_string()))?;
+    if driven >= pos.len() {
+        return Err(Error::Invalid(format!(
+            "the driven element {} is not in the cluster of {}",
+            driven,
+            pos.len()
+        )));
+    }
+    let p0 = pos[driven];
+    let offset = |k: usize, tag: i64| tag + k as i64 * ntags;
+
+    let wires: Vec<_> = lines
+        .iter()
+        .filter(|l| fields(l)[0] == "GW")
+        .copied()
+        .collect();
+    let last_wire = lines.iter().rposition(|l| fields(l)[0] == "GW");
+    let first_move = lines
+        .iter()
+        .position(|l| matches!(fields(l)[0], "GM" | "GS"));
+    if let (Some(w), Some(m)) = (last_wire, first_move) {
+        if m < w {
+            return Err(Error::Invalid(
+                "GW cards after a GM or GS card in the element deck".to_string(),
+            ));
+        }
+    }
+
+    let mut deck = Vec::new();
+    let mut wires_done = false;
+    for line in lines {
+        let f = fields(line);
+        match f[0] {
+            // all the wires of one copy before those of the next, as GM
+            // moves the segments from the first one with tag ITS to the end
+            // of the segment list
+            "GW" => {
+                if !wires_done {
+                    for k in 0..pos.len() {
+                        for w in &wires {
+                            deck.push(retag(w, &[1], offset(k, 0))?);
+                        }
+                    }
+                    wires_done = true;
+                }
+            }
+            "GE" => {
+                let mut prev = (0.0, 0.0);
+                for (k, p) in pos.iter().enumerate() {
+                    let rel = (p.0 - p0.0, p.1 - p0.1);
+                    deck.push(format!(
+                        "GM 0 0 0 0 0 {} {} 0 {}",
+                        rel.0 - prev.0,
+                        rel.1 - prev.1,
+                        offset(k, 1)
+                    ));
+                    prev = rel;
+                }
+                deck.push(line.to_string());
+            }
+            "GA" | "GH" | "SP" | "SM" | "SC" | "GR" | "GX" => {
+                return Err(Error::Invalid(format!(
+                    "unsupported card {} in the element deck",
+                    f[0]
+                )))
+            }
+            "TL" | "NT" => {
+                for k in 0..pos.len() {
+                    deck.push(retag(line, &[1, 3], offset(k, 0))?);
+                }
+            }
+            "EX" => {
+                let tag = parse_tag(f.get(2).copied().unwrap_or(""), line)?;
+                let seg = f.get(3).copied().unwrap_or("0");
+                for k in 0..pos.len() {
+                    if k == driven {
+                        deck.push(retag(line, &[2], offset(k, 0))?);
+                    } else {
+                        let t = offset(k, tag).to_string();
+                        deck.push(format!("LD 4 {} {} {} {} 0", t, seg, seg, load_ohm));
+                    }
+                }
+            }
+            _ => deck.push(line.to_string()),
+        }
+    }
+    Ok(deck.join("\n") + "\n")
+}
+
+/// offsets (east, north) of the other antennas within `radius` of antenna `i`
+pub fn neighbour_offsets(pos: &[(f64, f64, f64)], i: usize, radius: f64) -> Vec<(f64, f64)> {
+    pos.iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .map(|(_, p)| (p.0 - pos[i].0, p.1 - pos[i].1))
+        .filter(|d| d.0.hypot(d.1) <= radius)
+        .collect()
+}
+
+/// matched minus unmatched offsets, an offset matching when the other set
+/// has one within `tol`
+fn match_score(a: &[(f64, f64)], b: &[(f64, f64)], tol: f64) -> isize {
+    let matched = a
+        .iter()
+        .filter(|p| b.iter().any(|q| (p.0 - q.0).hypot(p.1 - q.1) <= tol))
+        .count() as isize;
+    2 * matched - a.len() as isize - b.len() as isize
+}
+
+/// for each antenna of `station`, the element among the `candidates` of
+/// `cluster` whose neighbours within `radius` are arranged most like its own
+/// (offsets matching within `tol`); the first candidate wins ties
+pub fn assign_embedded(
+    station: &ArrayCfg,
+    cluster: &ArrayCfg,
+    candidates: &[usize],
+    radius: f64,
+    tol: f64,
+) -> Vec<usize> {
+    let station_pos: Vec<_> = station.all_ants().iter().map(|a| a.pos).collect();
+    let cluster_pos: Vec<_> = cluster.all_ants().iter().map(|a| a.pos).collect();
+    let patterns: Vec<_> = candidates
+        .iter()
+        .map(|&c| neighbour_offsets(&cluster_pos, c, radius))
+        .collect();
+    (0..station_pos.len())
+        .map(|i| {
+            let own = neighbour_offsets(&station_pos, i, radius);
+            let best = patterns
+                .iter()
+                .enumerate()
+                .fold((0, isize::MIN), |best, (k, p)| {
+                    let score = match_score(&own, p, tol);
+                    if score > best.1 {
+                        (k, score)
+                    } else {
+                        best
+                    }
+                });
+            candidates[best.0]
+        })
+        .collect()
+}
+
+fn set_group_elements(
+    ants: &mut [AntCfg],
+    groups: &mut [GroupCfg],
+    names: &mut impl Iterator<Item = String>,
+) {
+    for a in ants {
+        a.element = names.next();
+    }
+    for g in groups {
+        set_group_elements(&mut g.ants, &mut g.groups, names);
+    }
+}
+
+/// sets the `element` of the antennas to `names`, given in the order of
+/// `ArrayCfg::all_ants`
+pub fn set_elements(cfg: &mut ArrayCfg, names: &[String]) {
+    set_group_elements(&mut cfg.ants, &mut cfg.groups, &mut names.iter().cloned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{layout_cfg, Layout};
+
+    const LP: &str = include_str!("../data/21cma_lp.nec");
+
+    fn cards<'a>(deck: &'a str, name: &str) -> Vec<Vec<&'a str>> {
+        deck.lines().map(fields).filter(|f| f[0] == name).collect()
+    }
+
+    #[test]
+    fn two_element_deck() {
+        for driven in 0..2 {
+            let deck = cluster_nec_deck(LP, &[(0.0, 0.0), (2.0, 0.0)], driven, 50.0).unwrap();
+            let tags: Vec<_> = cards(&deck, "GW").iter().map(|f| f[1]).collect();
+            let expected: Vec<_> = (1..=16)
+                .chain(17..=32)
+                .map(|t: i64| t.to_string())
+                .collect();
+            assert_eq!(tags, expected);
+
+            // the original tilt of the element, then the moves of the copies
+            // with the driven one at the origin
+            let gm: Vec<_> = cards(&deck, "GM").iter().map(|f| f.join(" ")).collect();
+            let shift = if driven == 0 { 0.0 } else { -2.0 };
+            assert_eq!(
+                gm[1..],
+                [
+                    format!("GM 0 0 0 0 0 {} 0 0 1", shift),
+                    "GM 0 0 0 0 0 2 0 0 17".to_string()
+                ]
+            );
+            let lines: Vec<_> = deck.lines().collect();
+            let ge = lines.iter().position(|l| l.starts_with("GE")).unwrap();
+            assert!(lines[ge - 1].starts_with("GM"));
+
+            let ex = cards(&deck, "EX");
+            assert_eq!(ex.len(), 1);
+            assert_eq!(ex[0][2], (16 * (driven + 1)).to_string());
+            let ld: Vec<_> = cards(&deck, "LD").iter().map(|f| f.join(" ")).collect();
+            assert_eq!(ld, [format!("LD 4 {} 8 8 50 0", 16 * (2 - driven))]);
+
+            let tl = cards(&deck, "TL");
+            assert_eq!(tl.len(), 30);
+            assert!(tl.iter().any(|f| f[1..5] == ["17", "8", "18", "8"]));
+            assert!(tl.iter().all(|f| {
+                let (a, b) = (f[1].parse::<i64>().unwrap(), f[3].parse::<i64>().unwrap());
+                (a - 1) / 16 == (b - 1) / 16
+            }));
+        }
+    }
+
+    #[test]
+    fn invalid_decks() {
+        assert!(cluster_nec_deck(LP, &[(0.0, 0.0)], 1, 50.0).is_err());
+        assert!(cluster_nec_deck("GE 0\nEN\n", &[(0.0, 0.0)], 0, 50.0).is_err());
+        assert!(cluster_nec_deck(
+            "GW 0 1 0 0 0 1 0 0 0.01\nGE 0\nEN\n",
+            &[(0.0, 0.0)],
+            0,
+            50.0
+        )
+        .is_err());
+        assert!(cluster_nec_deck(
+            "GW 1 1 0 0 0 1 0 0 0.01\nGM 0 0 90 0 0 0 0 0\nGW 2 1 0 0 0 1 0 0 0.01\nGE 0\nEN\n",
+            &[(0.0, 0.0)],
+            0,
+            50.0
+        )
+        .is_err());
+        assert!(cluster_nec_deck(
+            "GH 1 10 1 1 1 1 1 1 0.01\nGE 0\nEN\n",
+            &[(0.0, 0.0)],
+            0,
+            50.0
+        )
+        .is_err());
+    }
+
+    fn hex(spec: &str) -> ArrayCfg {
+        layout_cfg(&spec.parse::<Layout>().unwrap().positions().unwrap(), None)
+    }
+
+    fn index_of(cfg: &ArrayCfg, p: (f64, f64)) -> usize {
+        cfg.ants
+            .iter()
+            .position(|a| (a.pos.0 - p.0).hypot(a.pos.1 - p.1) < 1e-9)
+            .unwrap()
+    }
+
+    #[test]
+    fn centre_and_edges_of_a_hex_station() {
+        let cluster = hex("hex:1:1");
+        let station = hex("hex:1:2");
+        let candidates: Vec<_> = (0..cluster.ants.len()).collect();
+        let assigned = assign_embedded(&station, &cluster, &candidates, 1.5, 0.25);
+        let centre = index_of(&cluster, (0.0, 0.0));
+        for (a, &c) in station.ants.iter().zip(assigned.iter()) {
+            let r = a.pos.0.hypot(a.pos.1);
+            if r < 1.5 {
+                // surrounded by six neighbours
+                assert_eq!(c, centre);
+            } else if (r - 2.0).abs() < 1e-9 {
+                // a corner sees the neighbours of the cluster element in the
+                // same direction
+                assert_eq!(c, index_of(&cluster, (a.pos.0 / 2.0, a.pos.1 / 2.0)));
+            } else {
+                assert_ne!(c, centre);
+            }
+        }
+    }
+
+    #[test]
+    fn elements_follow_all_ants() {
+        let mut cfg = hex("hex:1:1");
+        let names: Vec<_> = (0..cfg.ants.len()).map(|i| format!("e{}", i)).collect();
+        set_elements(&mut cfg, &names);
+        for (a, n) in cfg.all_ants().iter().zip(names.iter()) {
+            assert_eq!(a.element.as_ref(), Some(n));
+        }
+    }
+}
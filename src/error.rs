@@ -0,0 +1,137 @@
+//! Errors of reading the inputs (beam files, configurations, sky models) and
+//! writing the outputs, with the file (and keyword or column) at fault.
+
+use std::fmt;
+
+use fitsio::{hdu::FitsHdu, headers::ReadsKey, FitsFile};
+
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    Fits {
+        path: String,
+        source: fitsio::errors::Error,
+    },
+    Yaml {
+        path: String,
+        source: serde_yaml::Error,
+    },
+    /// header keyword missing or of the wrong type
+    MissingKey {
+        path: String,
+        key: String,
+    },
+    MissingColumn {
+        path: String,
+        column: String,
+    },
+    /// readable input that cannot be used, e.g., beams of different nside
+    Invalid(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: &str, source: std::io::Error) -> Self {
+        Error::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn fits(path: &str, source: fitsio::errors::Error) -> Self {
+        Error::Fits {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn yaml(path: &str, source: serde_yaml::Error) -> Self {
+        Error::Yaml {
+            path: path.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::Fits { path, source } => write!(f, "{}: {}", path, source),
+            Error::Yaml { path, source } => write!(f, "{}: {}", path, source),
+            Error::MissingKey { path, key } => {
+                write!(f, "{}: missing or invalid keyword {}", path, key)
+            }
+            Error::MissingColumn { path, column } => write!(f, "{}: no column {}", path, column),
+            Error::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Fits { source, .. } => Some(source),
+            Error::Yaml { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// attach the file to errors of fitsio, io and serde_yaml
+pub trait WithPath<T> {
+    fn with_path(self, path: &str) -> Result<T>;
+}
+
+impl<T> WithPath<T> for std::result::Result<T, fitsio::errors::Error> {
+    fn with_path(self, path: &str) -> Result<T> {
+        self.map_err(|e| Error::fits(path, e))
+    }
+}
+
+impl<T> WithPath<T> for std::result::Result<T, std::io::Error> {
+    fn with_path(self, path: &str) -> Result<T> {
+        self.map_err(|e| Error::io(path, e))
+    }
+}
+
+impl<T> WithPath<T> for std::result::Result<T, serde_yaml::Error> {
+    fn with_path(self, path: &str) -> Result<T> {
+        self.map_err(|e| Error::yaml(path, e))
+    }
+}
+
+/// header keyword of `hdu` in the file `path`
+pub fn read_key<T: ReadsKey>(
+    fitsfile: &mut FitsFile,
+    hdu: &FitsHdu,
+    key: &str,
+    path: &str,
+) -> Result<T> {
+    hdu.read_key(fitsfile, key).map_err(|_| Error::MissingKey {
+        path: path.to_string(),
+        key: key.to_string(),
+    })
+}
+
+/// frequency keyword of `hdu`, rejected unless finite and positive
+pub fn read_freq_key(fitsfile: &mut FitsFile, hdu: &FitsHdu, key: &str, path: &str) -> Result<f64> {
+    let freq = read_key::<f64>(fitsfile, hdu, key, path)?;
+    if freq.is_finite() && freq > 0.0 {
+        Ok(freq)
+    } else {
+        Err(Error::Invalid(format!(
+            "{}: {} = {} is not a valid frequency",
+            path, key, freq
+        )))
+    }
+}
+
+pub fn open_fits(path: &str) -> Result<FitsFile> {
+    FitsFile::open(path).with_path(path)
+}
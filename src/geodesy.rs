@@ -0,0 +1,148 @@
+//! WGS84 geodetic and earth-centred, earth-fixed (ECEF) coordinates, and the
+//! local east-north-up frame of a site. ITRF positions are taken as ECEF, the
+//! difference being far below the accuracy needed for antenna positions.
+
+use crate::cfg::SiteCfg;
+
+/// semi-major axis in m
+pub const WGS84_A: f64 = 6378137.0;
+/// flattening
+pub const WGS84_F: f64 = 1.0 / 298.257223563;
+
+fn e2() -> f64 {
+    WGS84_F * (2.0 - WGS84_F)
+}
+
+/// ECEF position in m of a point at longitude and latitude (in rad) and
+/// height above the ellipsoid (in m)
+pub fn geodetic_to_ecef(lon: f64, lat: f64, height: f64) -> (f64, f64, f64) {
+    let (sin_lat, cos_lat) = lat.sin_cos();
+    let (sin_lon, cos_lon) = lon.sin_cos();
+    let n = WGS84_A / (1.0 - e2() * sin_lat * sin_lat).sqrt();
+    (
+        (n + height) * cos_lat * cos_lon,
+        (n + height) * cos_lat * sin_lon,
+        (n * (1.0 - e2()) + height) * sin_lat,
+    )
+}
+
+/// longitude and latitude (in rad) and height (in m) of an ECEF position
+pub fn ecef_to_geodetic(p: (f64, f64, f64)) -> (f64, f64, f64) {
+    let (x, y, z) = p;
+    let lon = y.atan2(x);
+    let r = x.hypot(y);
+    let mut lat = z.atan2(r * (1.0 - e2()));
+    let mut height = 0.0;
+    // converges to well below a mm in a few iterations for points near the
+    // surface
+    for _ in 0..10 {
+        let n = WGS84_A / (1.0 - e2() * lat.sin().powi(2)).sqrt();
+        height = if lat.cos().abs() > 1e-9 {
+            r / lat.cos() - n
+        } else {
+            z.abs() - n * (1.0 - e2())
+        };
+        lat = z.atan2(r * (1.0 - e2() * n / (n + height)));
+    }
+    (lon, lat, height)
+}
+
+impl SiteCfg {
+    pub fn from_ecef(p: (f64, f64, f64)) -> Self {
+        let (lon, lat, height) = ecef_to_geodetic(p);
+        Self {
+            lon_deg: lon.to_degrees(),
+            lat_deg: lat.to_degrees(),
+            height_m: height,
+        }
+    }
+
+    pub fn ecef(&self) -> (f64, f64, f64) {
+        geodetic_to_ecef(
+            self.lon_deg.to_radians(),
+            self.lat_deg.to_radians(),
+            self.height_m,
+        )
+    }
+
+    /// east, north and up (in m) of an ECEF position relative to the site
+    pub fn ecef_to_enu(&self, p: (f64, f64, f64)) -> (f64, f64, f64) {
+        let o = self.ecef();
+        let (dx, dy, dz) = (p.0 - o.0, p.1 - o.1, p.2 - o.2);
+        let (sin_lat, cos_lat) = self.lat_deg.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.lon_deg.to_radians().sin_cos();
+        (
+            -sin_lon * dx + cos_lon * dy,
+            -sin_lat * cos_lon * dx - sin_lat * sin_lon * dy + cos_lat * dz,
+            cos_lat * cos_lon * dx + cos_lat * sin_lon * dy + sin_lat * dz,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the site of data/21cma_station.yaml
+    fn site() -> SiteCfg {
+        SiteCfg {
+            lon_deg: 86.72,
+            lat_deg: 42.552673743,
+            height_m: 2650.0,
+        }
+    }
+
+    #[test]
+    fn ecef_of_the_equator_and_the_pole() {
+        let (x, y, z) = geodetic_to_ecef(0.0, 0.0, 0.0);
+        assert!((x - WGS84_A).abs() < 1e-6 && y.abs() < 1e-6 && z.abs() < 1e-6);
+        // semi-minor axis
+        let (x, y, z) = geodetic_to_ecef(1.0, std::f64::consts::FRAC_PI_2, 0.0);
+        assert!(x.abs() < 1e-6 && y.abs() < 1e-6 && (z - 6356752.314245).abs() < 1e-6);
+    }
+
+    #[test]
+    fn geodetic_ecef_round_trip() {
+        for lat_deg in [-90.0, -60.0, -1e-3, 0.0, 23.4, 42.55, 89.9, 90.0_f64] {
+            for lon_deg in [-179.0, 0.0, 86.72_f64] {
+                for height in [-100.0, 0.0, 2650.0, 1e4] {
+                    let (lon, lat) = (lon_deg.to_radians(), lat_deg.to_radians());
+                    let (lon1, lat1, height1) =
+                        ecef_to_geodetic(geodetic_to_ecef(lon, lat, height));
+                    assert!((lat1 - lat).abs() < 1e-11);
+                    assert!(lat_deg.abs() == 90.0 || (lon1 - lon).abs() < 1e-12);
+                    assert!((height1 - height).abs() < 1e-6);
+                }
+            }
+        }
+        let s = SiteCfg::from_ecef(site().ecef());
+        assert!((s.lat_deg - site().lat_deg).abs() < 1e-9);
+        assert!((s.lon_deg - site().lon_deg).abs() < 1e-9);
+        assert!((s.height_m - site().height_m).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ecef_to_enu_at_the_site() {
+        let site = site();
+        let (lon, lat) = (site.lon_deg.to_radians(), site.lat_deg.to_radians());
+        let enu = |lon, lat, height| site.ecef_to_enu(geodetic_to_ecef(lon, lat, height));
+        let close = |a: (f64, f64, f64), b: (f64, f64, f64), tol: f64| {
+            (a.0 - b.0).abs() < tol && (a.1 - b.1).abs() < tol && (a.2 - b.2).abs() < tol
+        };
+        assert!(close(enu(lon, lat, site.height_m), (0.0, 0.0, 0.0), 1e-6));
+        // along the normal of the ellipsoid
+        assert!(close(
+            enu(lon, lat, site.height_m + 100.0),
+            (0.0, 0.0, 100.0),
+            1e-6
+        ));
+        // 1e-5 rad in latitude and longitude, i.e. tens of m to the north and
+        // the east, with the curvature of the earth dropping them by mm
+        let north = enu(lon, lat + 1e-5, site.height_m);
+        assert!(north.0.abs() < 1e-6 && north.1 > 63.0 && north.1 < 64.0);
+        assert!(north.2 < 0.0 && north.2 > -1e-3);
+        let east = enu(lon + 1e-5, lat, site.height_m);
+        assert!(east.1.abs() < 1e-3 && east.0 > 46.5 && east.0 < 47.5);
+        assert!(east.2 < 0.0 && east.2 > -1e-3);
+    }
+}
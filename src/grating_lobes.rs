@@ -0,0 +1,249 @@
+//! Grating lobes of the array factor.
+//!
+//! The array factor is searched on a grid of direction cosines (l, m) =
+//! (east, north) reaching beyond the unit circle by the wavelength over the
+//! shortest baseline, so that the lobes about to enter the sky are found
+//! too; beyond the unit circle it is continued as the array factor of the
+//! horizontal positions. Any local maximum other than the main lobe above a
+//! threshold relative to the main lobe is reported.
+
+use std::f64::consts::PI;
+
+use scorus::coordinates::Vec3d;
+
+use crate::{arbitrary_array::ArrayFactor, coordinates::AltAz};
+
+/// the search goes at most this far in direction cosines
+const MAX_EXTENT: f64 = 3.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct GratingLobe {
+    /// direction cosines towards east and north
+    pub l: f64,
+    pub m: f64,
+    /// the altitude is negative beyond the horizon (l^2 + m^2 > 1), see
+    /// `lm_altitude`
+    pub dir: AltAz,
+    /// power relative to the main lobe
+    pub level: f64,
+}
+
+impl GratingLobe {
+    /// whether the lobe is above the horizon
+    pub fn visible(&self) -> bool {
+        self.dir.alt > 0.0
+    }
+}
+
+/// altitude of the direction cosines (l, m), i.e. acos(r) with r the
+/// distance from the zenith in direction cosines, continued beyond the
+/// horizon (r > 1) as -atan(sqrt(r^2 - 1) / r)
+pub fn lm_altitude(l: f64, m: f64) -> f64 {
+    let r = l.hypot(m);
+    (1.0 - r * r).abs().sqrt().atan2(r) * if r <= 1.0 { 1.0 } else { -1.0 }
+}
+
+/// direction of the direction cosines (l, m), on the horizon beyond the unit
+/// circle so that only the horizontal positions count
+fn lm_to_vec3d(l: f64, m: f64) -> Vec3d<f64> {
+    Vec3d {
+        x: l,
+        y: m,
+        z: (1.0 - l * l - m * m).max(0.0).sqrt(),
+    }
+}
+
+/// lobes of the array factor (with the arguments of `calc_array_beam1`)
+/// above `threshold` relative to the main lobe, the main lobe being the
+/// maximum closest to `pointing`; sorted by level.
+/// Lobes beyond the horizon are included, with a negative altitude.
+#[allow(clippy::too_many_arguments)]
+pub fn find_grating_lobes(
+    x_list: &[f64],
+    y_list: &[f64],
+    z_list: &[f64],
+    w_list: &[f64],
+    phi_list: &[f64],
+    lambda: f64,
+    pointing: &AltAz,
+    threshold: f64,
+) -> Vec<GratingLobe> {
+    let af = ArrayFactor::new(x_list, y_list, z_list, w_list, phi_list, lambda);
+    let power = |l: f64, m: f64| af.eval(&lm_to_vec3d(l, m)).norm_sqr();
+
+    // beyond the unit circle, the lobes closer than lambda / d to it
+    let extent = (1.0 + lambda / shortest_baseline(x_list, y_list, w_list)).min(MAX_EXTENT);
+    // a few samples per beam width
+    let (xmin, xmax) = min_max(x_list);
+    let (ymin, ymax) = min_max(y_list);
+    let size = (xmax - xmin).hypot(ymax - ymin).max(lambda);
+    let step = lambda / size / 4.0;
+    let n = (2.0 * extent / step).ceil() as usize + 1;
+    let coord = |i: usize| -extent + i as f64 * step;
+    let dirs: Vec<_> = (0..n * n)
+        .map(|k| lm_to_vec3d(coord(k / n), coord(k % n)))
+        .collect();
+    let grid: Vec<_> = af.eval_many(&dirs).iter().map(|a| a.norm_sqr()).collect();
+    let at = |i: usize, j: usize| grid[i * n + j];
+
+    // interior local maxima, refined by a local search with shrinking steps
+    let mut peaks: Vec<(f64, f64, f64)> = Vec::new();
+    for i in 1..n - 1 {
+        for j in 1..n - 1 {
+            let p = at(i, j);
+            let is_max = (i - 1..=i + 1).all(|a| (j - 1..=j + 1).all(|b| at(a, b) <= p));
+            if !is_max {
+                continue;
+            }
+            let (mut l, mut m, mut p) = (coord(i), coord(j), p);
+            let mut s = step / 2.0;
+            while s > step / 1024.0 {
+                for (dl, dm) in [(s, 0.0), (-s, 0.0), (0.0, s), (0.0, -s)] {
+                    let q = power(l + dl, m + dm);
+                    if q > p {
+                        (l, m, p) = (l + dl, m + dm, q);
+                    }
+                }
+                s /= 2.0;
+            }
+            // the cells of a plateau lead to the same peak
+            match peaks.iter_mut().find(|q| (q.0 - l).hypot(q.1 - m) < step) {
+                Some(q) if q.2 < p => *q = (l, m, p),
+                Some(_) => {}
+                None => peaks.push((l, m, p)),
+            }
+        }
+    }
+    if peaks.is_empty() {
+        return Vec::new();
+    }
+
+    let v0 = pointing.to_vec3d();
+    let d = |k: usize| (peaks[k].0 - v0.x).hypot(peaks[k].1 - v0.y);
+    let imain = (0..peaks.len())
+        .min_by(|&a, &b| d(a).total_cmp(&d(b)))
+        .unwrap();
+    let p0 = peaks[imain].2;
+    let mut lobes: Vec<_> = peaks
+        .iter()
+        .enumerate()
+        .filter(|&(k, &(_, _, p))| k != imain && p >= threshold * p0)
+        .map(|(_, &(l, m, p))| GratingLobe {
+            l,
+            m,
+            dir: AltAz::new(lm_altitude(l, m), l.atan2(m).rem_euclid(2.0 * PI)),
+            level: p / p0,
+        })
+        .collect();
+    lobes.sort_by(|a, b| b.level.total_cmp(&a.level));
+    lobes
+}
+
+/// shortest horizontal distance between antennas with a non-zero weight,
+/// infinite for fewer than two
+fn shortest_baseline(x_list: &[f64], y_list: &[f64], w_list: &[f64]) -> f64 {
+    let pos: Vec<_> = (0..x_list.len())
+        .filter(|&i| w_list[i] != 0.0)
+        .map(|i| (x_list[i], y_list[i]))
+        .collect();
+    let mut d = f64::INFINITY;
+    for (i, a) in pos.iter().enumerate() {
+        for b in &pos[i + 1..] {
+            let dist = (a.0 - b.0).hypot(a.1 - b.1);
+            if dist > 0.0 {
+                d = d.min(dist);
+            }
+        }
+    }
+    d
+}
+
+fn min_max(x: &[f64]) -> (f64, f64) {
+    x.iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(a, b), &x| {
+            (a.min(x), b.max(x))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{arbitrary_array::calc_phase_from_pointing, constants::LIGHT_SPEED as C};
+
+    /// 8 x 8 antennas spaced by 2 m, steered towards `pointing`
+    fn lobes(freq: f64, pointing: &AltAz) -> Vec<GratingLobe> {
+        let (mut x, mut y) = (Vec::new(), Vec::new());
+        for i in 0..8 {
+            for j in 0..8 {
+                x.push(2.0 * i as f64);
+                y.push(2.0 * j as f64);
+            }
+        }
+        let z = vec![0.0; x.len()];
+        let w = vec![1.0; x.len()];
+        let phi = calc_phase_from_pointing(&x, &y, &z, pointing, C / freq);
+        find_grating_lobes(&x, &y, &z, &w, &phi, C / freq, pointing, 0.5)
+    }
+
+    /// first frequency of a grid of 1% steps from 0.95 `f` at which a lobe
+    /// is above the horizon
+    fn first_visible(f: f64, pointing: &AltAz) -> f64 {
+        (0..10)
+            .map(|i| f * (0.95 + 0.01 * i as f64))
+            .find(|&freq| lobes(freq, pointing).iter().any(|g| g.visible()))
+            .unwrap()
+    }
+
+    #[test]
+    fn zenith_lobes_reach_the_horizon_at_lambda_equal_spacing() {
+        let zenith = AltAz::new(std::f64::consts::FRAC_PI_2, 0.0);
+        // lambda = d
+        let f = C / 2.0;
+        assert!((first_visible(f, &zenith) / f - 1.0).abs() < 0.011);
+
+        // below, the four lobes nearest to the main one are beyond the horizon
+        let below = lobes(0.95 * f, &zenith);
+        assert!(!below.is_empty() && below.iter().all(|g| !g.visible()));
+        let r: f64 = 1.0 / 0.95;
+        let alt = -((r * r - 1.0).sqrt() / r).atan();
+        assert_eq!(
+            below
+                .iter()
+                .filter(|g| (g.l.hypot(g.m) - r).abs() < 1e-4 && (g.dir.alt - alt).abs() < 1e-3)
+                .count(),
+            4
+        );
+
+        // above, they are at a zenith angle of asin(lambda / d)
+        let above = lobes(1.05 * f, &zenith);
+        let r = 1.0 / 1.05;
+        let visible: Vec<_> = above.iter().filter(|g| g.visible()).collect();
+        assert_eq!(visible.len(), 4);
+        assert!(visible.iter().all(|g| (g.l.hypot(g.m) - r).abs() < 1e-4));
+        assert!(visible.iter().all(|g| (g.dir.alt - r.acos()).abs() < 1e-3));
+        assert!(visible.iter().all(|g| g.level > 0.99));
+    }
+
+    #[test]
+    fn steered_lobe_enters_opposite_to_the_pointing() {
+        // towards the east at a zenith angle of 30 deg
+        let zenith_angle = 30f64.to_radians();
+        let ptg = AltAz::from_zenith_az(zenith_angle, 90f64.to_radians());
+        // lambda / d = 1 + sin(zenith angle)
+        let f = C / 2.0 / (1.0 + zenith_angle.sin());
+        assert!((first_visible(f, &ptg) / f - 1.0).abs() < 0.011);
+
+        let above = lobes(1.02 * f, &ptg);
+        let west = above.iter().find(|g| g.visible()).unwrap();
+        assert!((west.dir.az.to_degrees() - 270.0).abs() < 0.5);
+        assert!(west.m.abs() < 1e-3);
+    }
+
+    #[test]
+    fn altitude_is_continuous_at_the_horizon() {
+        assert!((lm_altitude(0.0, 0.0) - std::f64::consts::FRAC_PI_2).abs() < 1e-15);
+        assert!((lm_altitude(0.6, 0.8)).abs() < 1e-7);
+        assert!(lm_altitude(0.6, 0.8 + 1e-9) < 0.0 && lm_altitude(0.6, 0.8 + 1e-9) > -1e-3);
+        assert!((lm_altitude(0.5, 0.0) - 60f64.to_radians()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,262 @@
+//! Generators of station layouts, as positions east and north (in m) of the
+//! station centre, to be written as `ArrayCfg` files.
+
+use std::{collections::BTreeMap, f64::consts::PI, str::FromStr};
+
+use crate::{
+    cfg::{AntCfg, ArrayCfg, GainCfg, GroupCfg, LengthUnit, PosFrame},
+    error::{Error, Result},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Layout {
+    /// hexagonal grid with rows along east-west and `rings` rings around the
+    /// central antenna, i.e., 3 rings (rings + 1) + 1 antennas
+    Hex { spacing: f64, rings: usize },
+    /// `n` by `n` grid
+    Square { spacing: f64, n: usize },
+    /// `n` antennas evenly spaced on a circle, the first one north of the centre
+    Ring { radius: f64, n: usize },
+    /// `n` antennas uniformly distributed in a disk, no two closer than
+    /// `min_spacing`
+    Random {
+        radius: f64,
+        n: usize,
+        min_spacing: f64,
+        seed: u64,
+    },
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    /// `hex:<spacing>:<rings>`, `square:<spacing>:<n>`, `ring:<radius>:<n>`
+    /// or `random:<radius>:<n>:<min spacing>[:<seed>]`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let items: Vec<_> = s.split(':').collect();
+        let item = |i: usize| -> std::result::Result<&str, String> {
+            items
+                .get(i)
+                .copied()
+                .ok_or(format!("missing parameter in layout {}", s))
+        };
+        let num = |i: usize| -> std::result::Result<f64, String> {
+            item(i)?
+                .parse::<f64>()
+                .map_err(|e| format!("invalid parameter in layout {}: {}", s, e))
+        };
+        let count = |i: usize| -> std::result::Result<usize, String> {
+            item(i)?
+                .parse::<usize>()
+                .map_err(|e| format!("invalid parameter in layout {}: {}", s, e))
+        };
+        let layout = match items[0] {
+            "hex" => Layout::Hex {
+                spacing: num(1)?,
+                rings: count(2)?,
+            },
+            "square" => Layout::Square {
+                spacing: num(1)?,
+                n: count(2)?,
+            },
+            "ring" => Layout::Ring {
+                radius: num(1)?,
+                n: count(2)?,
+            },
+            "random" => Layout::Random {
+                radius: num(1)?,
+                n: count(2)?,
+                min_spacing: num(3)?,
+                seed: match items.get(4) {
+                    Some(x) => x
+                        .parse::<u64>()
+                        .map_err(|e| format!("invalid seed in layout {}: {}", s, e))?,
+                    None => 0,
+                },
+            },
+            _ => return Err(format!("unknown layout {}", s)),
+        };
+        Ok(layout)
+    }
+}
+
+/// splitmix64, so that a seed gives the same layout on every platform
+struct Rng(u64);
+
+impl Rng {
+    /// uniform in [0, 1)
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// attempts to place each antenna of a random layout before giving up
+const MAX_TRIALS: usize = 100000;
+
+impl Layout {
+    /// positions east and north in m, from south to north and west to east
+    /// for the grids
+    pub fn positions(&self) -> Result<Vec<(f64, f64)>> {
+        let pos = match *self {
+            Layout::Hex { spacing, rings } => {
+                let r = rings as isize;
+                let mut pos = Vec::new();
+                for j in -r..=r {
+                    for i in (-r).max(-r - j)..=r.min(r - j) {
+                        pos.push((
+                            spacing * (i as f64 + j as f64 / 2.0),
+                            spacing * j as f64 * 3_f64.sqrt() / 2.0,
+                        ));
+                    }
+                }
+                pos
+            }
+            Layout::Square { spacing, n } => {
+                let c = (n as f64 - 1.0) / 2.0;
+                (0..n * n)
+                    .map(|k| {
+                        (
+                            spacing * ((k % n) as f64 - c),
+                            spacing * ((k / n) as f64 - c),
+                        )
+                    })
+                    .collect()
+            }
+            Layout::Ring { radius, n } => (0..n)
+                .map(|k| {
+                    let az = 2.0 * PI * k as f64 / n as f64;
+                    (radius * az.sin(), radius * az.cos())
+                })
+                .collect(),
+            Layout::Random {
+                radius,
+                n,
+                min_spacing,
+                seed,
+            } => {
+                let mut rng = Rng(seed);
+                let mut pos: Vec<(f64, f64)> = Vec::with_capacity(n);
+                while pos.len() < n {
+                    let p = (0..MAX_TRIALS)
+                        .map(|_| {
+                            let (r, phi) = (radius * rng.next().sqrt(), 2.0 * PI * rng.next());
+                            (r * phi.cos(), r * phi.sin())
+                        })
+                        .find(|p| {
+                            pos.iter()
+                                .all(|q| (p.0 - q.0).hypot(p.1 - q.1) >= min_spacing)
+                        })
+                        .ok_or_else(|| {
+                            Error::Invalid(format!(
+                                "only {} of {} antennas fit in a radius of {} m at a spacing of {} m",
+                                pos.len(),
+                                n,
+                                radius,
+                                min_spacing
+                            ))
+                        })?;
+                    pos.push(p);
+                }
+                pos
+            }
+        };
+        Ok(pos)
+    }
+}
+
+/// positions rotated by `rotation` (in rad, from north towards east, i.e., the
+/// way azimuths increase) about the origin and then shifted by `offset`
+pub fn transform(pos: &[(f64, f64)], rotation: f64, offset: (f64, f64)) -> Vec<(f64, f64)> {
+    let (s, c) = rotation.sin_cos();
+    pos.iter()
+        .map(|&(e, n)| (e * c + n * s + offset.0, -e * s + n * c + offset.1))
+        .collect()
+}
+
+/// single-level array of antennas at `pos`, or, with `pod`, an array of
+/// groups centred at `pos`, each with antennas at the positions of `pod`
+pub fn layout_cfg(pos: &[(f64, f64)], pod: Option<&[(f64, f64)]>) -> ArrayCfg {
+    let ants = |pos: &[(f64, f64)]| pos.iter().map(|&(e, n)| AntCfg::new((e, n, 0.0))).collect();
+    match pod {
+        None => ArrayCfg {
+            frame: PosFrame::Enu,
+            units: LengthUnit::M,
+            ants: ants(pos),
+            groups: Vec::new(),
+            elements: BTreeMap::new(),
+            site: None,
+        },
+        Some(pod) => ArrayCfg {
+            frame: PosFrame::Enu,
+            units: LengthUnit::M,
+            ants: Vec::new(),
+            groups: pos
+                .iter()
+                .map(|&(e, n)| GroupCfg {
+                    center: (e, n, 0.0),
+                    gain: GainCfg::default(),
+                    ants: ants(pod),
+                    groups: Vec::new(),
+                })
+                .collect(),
+            elements: BTreeMap::new(),
+            site: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_antenna_count_and_spacing() {
+        for rings in 0..6 {
+            let pos = Layout::Hex {
+                spacing: 2.5,
+                rings,
+            }
+            .positions()
+            .unwrap();
+            assert_eq!(pos.len(), 3 * rings * (rings + 1) + 1);
+            let (se, sn) = pos
+                .iter()
+                .fold((0.0, 0.0), |(se, sn), p| (se + p.0, sn + p.1));
+            assert!(se.abs() < 1e-9 && sn.abs() < 1e-9);
+            // six neighbours at the spacing for the inner antennas
+            let neighbours = |p: &(f64, f64)| {
+                pos.iter()
+                    .filter(|q| ((p.0 - q.0).hypot(p.1 - q.1) - 2.5).abs() < 1e-9)
+                    .count()
+            };
+            assert!(pos.iter().all(|p| neighbours(p) <= 6));
+            if rings > 0 {
+                assert_eq!(neighbours(&(0.0, 0.0)), 6);
+            }
+            for (i, p) in pos.iter().enumerate() {
+                assert!(pos[i + 1..]
+                    .iter()
+                    .all(|q| (p.0 - q.0).hypot(p.1 - q.1) > 2.5 - 1e-9));
+            }
+        }
+    }
+
+    #[test]
+    fn transform_rotates_the_way_azimuths_increase() {
+        let rot = 30_f64.to_radians();
+        // north goes to an azimuth of 30 deg, east to 120 deg
+        let out = transform(&[(0.0, 2.0), (2.0, 0.0)], rot, (0.0, 0.0));
+        for (p, az) in out.iter().zip([30_f64, 120.0]) {
+            assert!((p.0.atan2(p.1) - az.to_radians()).abs() < 1e-12);
+            assert!((p.0.hypot(p.1) - 2.0).abs() < 1e-12);
+        }
+        // the offset is applied after the rotation
+        let out = transform(&[(0.0, 1.0)], PI / 2.0, (10.0, -5.0));
+        assert!((out[0].0 - 11.0).abs() < 1e-12 && (out[0].1 + 5.0).abs() < 1e-12);
+    }
+}
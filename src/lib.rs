@@ -1,6 +1,20 @@
 pub mod arbitrary_array;
+pub mod beam;
+pub mod beam_metrics;
+pub mod cfg;
 pub mod constants;
+pub mod coordinates;
 pub mod dipole;
-pub mod utils;
-pub mod cfg;
-pub mod single_ant_model;
\ No newline at end of file
+pub mod embedded;
+pub mod error;
+pub mod geodesy;
+pub mod grating_lobes;
+pub mod layout;
+pub mod parallel;
+pub mod projection;
+pub mod single_ant_model;
+pub mod sky;
+pub mod sky_model;
+pub mod taper;
+pub mod validation;
+pub mod visibility;
@@ -1,2 +1,91 @@
+mod cmd;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(
+        long = "threads",
+        global = true,
+        value_name = "num of threads, all cores if not given"
+    )]
+    threads: Option<usize>,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// simulate the single antenna power beam with NEC
+    AntBeam(cmd::ant_beam::Args),
+
+    /// beam-weighted sky temperature seen by the array over frequency and time
+    AntennaTemperature(cmd::antenna_temperature::Args),
+
+    /// array power beam on the full sky in healpix
+    ArrayBeamFullsky(cmd::array_beam_fullsky::Args),
+
+    /// array beam on a patch centered on the phase center
+    ArrayBeamPatch(cmd::array_beam_patch::Args),
+
+    /// array power beam in healpix in equatorial or galactic coordinates
+    ArrayBeamSky(cmd::array_beam_sky::Args),
+
+    /// peak, widths, solid angles and sidelobes of a beam per frequency
+    BeamMetrics(cmd::beam_metrics::Args),
+
+    /// array power beam in healpix over a range of sidereal times, drifting or tracking
+    DriftScan(cmd::drift_scan::Args),
+
+    /// embedded element patterns of a cluster simulated with NEC, assigned to the antennas of a station
+    EmbeddedBeam(cmd::embedded_beam::Args),
+
+    /// grating lobes of the array factor above the horizon over a range of frequencies
+    GratingLobes(cmd::grating_lobes::Args),
+
+    /// array cfg from a csv table of enu, ecef (itrf) or geodetic antenna positions
+    Import(cmd::import::Args),
+
+    /// jones matrix of the array on a theta-phi grid
+    Jones(cmd::jones::Args),
+
+    /// array cfg with a hexagonal, square, ring or random layout
+    Layout(cmd::layout::Args),
+
+    /// duplicates, baselines, centroid, planarity and uv coverage of an array cfg
+    Validate(cmd::validate::Args),
+
+    /// visibilities between stations for point sources and a diffuse sky
+    Visibility(cmd::visibility::Args),
+}
+
+fn run(cli: Cli) -> jm21cma::error::Result<()> {
+    if let Some(n) = cli.threads {
+        jm21cma::parallel::set_num_threads(n)?;
+    }
+    match cli.command {
+        Command::AntBeam(args) => cmd::ant_beam::run(args),
+        Command::AntennaTemperature(args) => cmd::antenna_temperature::run(args),
+        Command::ArrayBeamFullsky(args) => cmd::array_beam_fullsky::run(args),
+        Command::ArrayBeamPatch(args) => cmd::array_beam_patch::run(args),
+        Command::ArrayBeamSky(args) => cmd::array_beam_sky::run(args),
+        Command::BeamMetrics(args) => cmd::beam_metrics::run(args),
+        Command::DriftScan(args) => cmd::drift_scan::run(args),
+        Command::EmbeddedBeam(args) => cmd::embedded_beam::run(args),
+        Command::GratingLobes(args) => cmd::grating_lobes::run(args),
+        Command::Import(args) => cmd::import::run(args),
+        Command::Jones(args) => cmd::jones::run(args),
+        Command::Layout(args) => cmd::layout::run(args),
+        Command::Validate(args) => cmd::validate::run(args),
+        Command::Visibility(args) => cmd::visibility::run(args),
+    }
+}
+
 fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,50 @@
+//! Optional parallel evaluation of independent samples (pixels, directions).
+//!
+//! With the `parallel` feature the samples are spread over the threads of the
+//! global rayon pool, otherwise they are evaluated one after the other. Each
+//! sample is computed by the same code in both cases and the results keep the
+//! order of the indices, so that the outputs are bit-identical.
+
+use std::ops::Range;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+#[cfg(feature = "parallel")]
+use crate::error::Error;
+use crate::error::Result;
+
+/// `f` evaluated at each index of `range`, in order
+#[cfg(feature = "parallel")]
+pub fn par_map<U, F>(range: Range<usize>, f: F) -> Vec<U>
+where
+    U: Send,
+    F: Fn(usize) -> U + Sync + Send,
+{
+    range.into_par_iter().map(f).collect()
+}
+
+/// `f` evaluated at each index of `range`, in order
+#[cfg(not(feature = "parallel"))]
+pub fn par_map<U, F>(range: Range<usize>, f: F) -> Vec<U>
+where
+    U: Send,
+    F: Fn(usize) -> U + Sync + Send,
+{
+    range.map(f).collect()
+}
+
+/// size of the thread pool used by `par_map`, to be called once before any
+/// parallel evaluation; no effect without the `parallel` feature
+pub fn set_num_threads(n: usize) -> Result<()> {
+    #[cfg(feature = "parallel")]
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(n)
+        .build_global()
+        .map_err(|e| Error::Invalid(format!("cannot start {} threads: {}", n, e)))?;
+    #[cfg(not(feature = "parallel"))]
+    if n > 1 {
+        eprintln!("built without the parallel feature, running on a single thread");
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Map projections of patches, following the FITS WCS conventions
+//! (Calabretta & Greisen 2002) with the default `LONPOLE`.
+//!
+//! Intermediate world coordinates `(x, y)` (in rad) are measured from the
+//! reference direction, `x` towards increasing longitude and `y` towards
+//! increasing latitude.
+
+use std::str::FromStr;
+
+use scorus::coordinates::Vec3d;
+
+use crate::{coordinates::AltAz, sky::SkyFrame};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Sin,
+    Tan,
+    Arc,
+    Zea,
+    Car,
+}
+
+impl FromStr for Projection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "SIN" => Ok(Projection::Sin),
+            "TAN" => Ok(Projection::Tan),
+            "ARC" => Ok(Projection::Arc),
+            "ZEA" => Ok(Projection::Zea),
+            "CAR" => Ok(Projection::Car),
+            _ => Err(format!(
+                "unknown projection {}, use sin, tan, arc, zea or car",
+                s
+            )),
+        }
+    }
+}
+
+impl Projection {
+    /// the projection code of `CTYPEi`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Projection::Sin => "SIN",
+            Projection::Tan => "TAN",
+            Projection::Arc => "ARC",
+            Projection::Zea => "ZEA",
+            Projection::Car => "CAR",
+        }
+    }
+
+    /// components of the direction of `(x, y)` along the reference direction,
+    /// the x axis and the y axis; None outside of the projection
+    pub fn deproject(&self, x: f64, y: f64) -> Option<[f64; 3]> {
+        if let Projection::Car = self {
+            return if y.abs() <= std::f64::consts::FRAC_PI_2 && x.abs() <= std::f64::consts::PI {
+                Some([y.cos() * x.cos(), y.cos() * x.sin(), y.sin()])
+            } else {
+                None
+            };
+        }
+        // zenithal projections, distance from the reference direction
+        let r = x.hypot(y);
+        let zeta = match self {
+            Projection::Sin if r <= 1.0 => r.asin(),
+            Projection::Tan => r.atan(),
+            Projection::Arc if r <= std::f64::consts::PI => r,
+            Projection::Zea if r <= 2.0 => 2.0 * (r / 2.0).asin(),
+            _ => return None,
+        };
+        if r == 0.0 {
+            return Some([1.0, 0.0, 0.0]);
+        }
+        let s = zeta.sin() / r;
+        Some([zeta.cos(), s * x, s * y])
+    }
+}
+
+/// coordinates of a patch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PatchFrame {
+    AltAz,
+    Sky(SkyFrame),
+}
+
+impl FromStr for PatchFrame {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "altaz" => Ok(PatchFrame::AltAz),
+            _ => SkyFrame::from_str(s)
+                .map(PatchFrame::Sky)
+                .map_err(|_| format!("unknown frame {}, use altaz, eq or gal", s)),
+        }
+    }
+}
+
+impl PatchFrame {
+    /// the station frame uses the generic xLON/xLAT pair of WCS Paper II
+    /// (Calabretta & Greisen 2002) with x = A, the longitude being the
+    /// azimuth east of north
+    fn axis_names(&self) -> (&'static str, &'static str) {
+        match self {
+            PatchFrame::AltAz => ("ALON", "ALAT"),
+            PatchFrame::Sky(SkyFrame::Equatorial) => ("RA", "DEC"),
+            PatchFrame::Sky(SkyFrame::Galactic) => ("GLON", "GLAT"),
+        }
+    }
+
+    /// `CTYPE1` and `CTYPE2`, e.g., `RA---SIN` and `DEC--SIN`
+    pub fn ctypes(&self, proj: Projection) -> (String, String) {
+        let (lon, lat) = self.axis_names();
+        (
+            format!("{:-<5}{}", lon, proj.code()),
+            format!("{:-<5}{}", lat, proj.code()),
+        )
+    }
+
+    /// frame and projection of `CTYPE1`
+    pub fn from_ctype(ctype1: &str) -> Option<(Self, Projection)> {
+        let ctype1 = ctype1.trim();
+        let (name, proj) = (ctype1.get(..5)?, ctype1.get(5..)?.parse().ok()?);
+        [
+            PatchFrame::AltAz,
+            PatchFrame::Sky(SkyFrame::Equatorial),
+            PatchFrame::Sky(SkyFrame::Galactic),
+        ]
+        .into_iter()
+        .find(|f| name.trim_end_matches('-') == f.axis_names().0)
+        .map(|f| (f, proj))
+    }
+}
+
+/// reference direction of a patch with the unit vectors of increasing
+/// longitude and latitude there
+#[derive(Clone, Copy, Debug)]
+pub struct TangentFrame {
+    pub center: Vec3d<f64>,
+    pub lon: Vec3d<f64>,
+    pub lat: Vec3d<f64>,
+}
+
+impl TangentFrame {
+    /// frame of a point at longitude `lon` and latitude `lat` (in rad) of a
+    /// sphere with the usual right-handed longitude
+    pub fn from_lon_lat(lon: f64, lat: f64) -> Self {
+        Self {
+            center: Vec3d {
+                x: lat.cos() * lon.cos(),
+                y: lat.cos() * lon.sin(),
+                z: lat.sin(),
+            },
+            lon: Vec3d {
+                x: -lon.sin(),
+                y: lon.cos(),
+                z: 0.0,
+            },
+            lat: Vec3d {
+                x: -lat.sin() * lon.cos(),
+                y: -lat.sin() * lon.sin(),
+                z: lat.cos(),
+            },
+        }
+    }
+
+    /// frame of a direction in the station frame, the longitude being the
+    /// azimuth east of north, i.e., `lon` points to increasing azimuth
+    pub fn from_altaz(altaz: &AltAz) -> Self {
+        let mut frame = Self::from_lon_lat(std::f64::consts::FRAC_PI_2 - altaz.az, altaz.alt);
+        frame.lon = frame.lon * -1.0;
+        frame
+    }
+
+    /// direction of the components returned by `Projection::deproject`
+    pub fn to_vec3d(&self, c: &[f64; 3]) -> Vec3d<f64> {
+        self.center * c[0] + self.lon * c[1] + self.lat * c[2]
+    }
+}
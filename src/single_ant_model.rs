@@ -1,12 +1,49 @@
 #![allow(non_snake_case)]
 
+use std::str::FromStr;
 
-use fitsio::FitsFile;
+use fitsio::hdu::{FitsHdu, HduInfo};
 use healpix_fits::read_map;
+use num::complex::Complex;
 use scorus::{
     coordinates::SphCoord,
     healpix::{interp::natural_interp_ring, npix2nside},
 };
+
+use crate::error::{open_fits, read_freq_key, Error, Result, WithPath};
+
+fn has_column(hdu: &FitsHdu, name: &str) -> bool {
+    match &hdu.info {
+        HduInfo::TableInfo {
+            column_descriptions,
+            ..
+        } => column_descriptions.iter().any(|c| c.name == name),
+        _ => false,
+    }
+}
+
+/// error unless all `columns` are in the table of `hdu`
+fn check_columns(hdu: &FitsHdu, columns: &[&str], fname: &str) -> Result<()> {
+    match columns.iter().find(|c| !has_column(hdu, c)) {
+        Some(c) => Err(Error::MissingColumn {
+            path: fname.to_string(),
+            column: c.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// the `TEMPERATURE` column of the extension `ihdu`
+fn read_temperature(fname: &str, ihdu: usize) -> Result<Vec<f64>> {
+    read_map::<f64>(fname, &["TEMPERATURE"], ihdu)
+        .pop()
+        .ok_or_else(|| Error::MissingColumn {
+            path: fname.to_string(),
+            column: "TEMPERATURE".to_string(),
+        })
+}
+
+#[derive(Clone)]
 pub struct SingleAnt {
     pub data: Vec<f64>,
     pub nside: usize,
@@ -23,15 +60,387 @@ impl SingleAnt {
         }
     }
 
-    pub fn from_fits(fname: &str) -> Self {
-        let data = read_map::<f64>(fname, &["TEMPERATURE"], 1).pop().unwrap();
-        let mut fitsfile = FitsFile::open(fname).unwrap();
-        let hdu = fitsfile.hdu(1).unwrap();
-        let freq_MHz=hdu.read_key::<f64>(&mut fitsfile, "FREQ_MHZ").unwrap();
-        Self::new(data, freq_MHz)
+    pub fn from_fits(fname: &str) -> Result<Self> {
+        let mut fitsfile = open_fits(fname)?;
+        let hdu = fitsfile.hdu(1).with_path(fname)?;
+        check_columns(&hdu, &["TEMPERATURE"], fname)?;
+        let freq_MHz = read_freq_key(&mut fitsfile, &hdu, "FREQ_MHZ", fname)?;
+        let data = read_temperature(fname, 1)?;
+        Ok(Self::new(data, freq_MHz))
     }
 
     pub fn power_pattern(&self, az: f64, pol: f64) -> f64 {
         natural_interp_ring(self.nside, &self.data, SphCoord::new(pol, az))
     }
 }
+
+/// names of the columns holding E_theta and E_phi of the feeds along x and y,
+/// stored in the `EFIELD` extension of the single antenna beam
+pub const EFIELD_COLUMNS: [&str; 8] = [
+    "X_ETHETA_RE",
+    "X_ETHETA_IM",
+    "X_EPHI_RE",
+    "X_EPHI_IM",
+    "Y_ETHETA_RE",
+    "Y_ETHETA_IM",
+    "Y_EPHI_RE",
+    "Y_EPHI_IM",
+];
+
+/// complex far field of the two feeds of a single antenna
+#[derive(Clone)]
+pub struct SingleAntPol {
+    /// real and imaginary parts, in the order of `EFIELD_COLUMNS`
+    pub data: Vec<Vec<f64>>,
+    pub nside: usize,
+    pub freq_MHz: f64,
+}
+
+impl SingleAntPol {
+    /// fails unless there is one map of the same size per column of
+    /// `EFIELD_COLUMNS`
+    pub fn new(data: Vec<Vec<f64>>, freq_MHz: f64) -> Result<Self> {
+        if data.len() != EFIELD_COLUMNS.len() {
+            return Err(Error::Invalid(format!(
+                "{} E field maps instead of {}",
+                data.len(),
+                EFIELD_COLUMNS.len()
+            )));
+        }
+        if data.iter().any(|d| d.len() != data[0].len()) {
+            return Err(Error::Invalid(
+                "the E field maps have different sizes".to_string(),
+            ));
+        }
+        let nside = npix2nside(data[0].len());
+        Ok(Self {
+            data,
+            nside,
+            freq_MHz,
+        })
+    }
+
+    pub fn from_fits(fname: &str) -> Result<Self> {
+        let mut fitsfile = open_fits(fname)?;
+        let hdu = fitsfile.hdu(1).with_path(fname)?;
+        let freq_MHz = read_freq_key(&mut fitsfile, &hdu, "FREQ_MHZ", fname)?;
+        let efield = fitsfile.hdu(2).with_path(fname)?;
+        check_columns(&efield, &EFIELD_COLUMNS, fname)?;
+        let data = read_map::<f64>(fname, &EFIELD_COLUMNS, 2);
+        Self::new(data, freq_MHz).map_err(|e| Error::Invalid(format!("{}: {}", fname, e)))
+    }
+
+    /// None if the beam file only contains the power pattern
+    pub fn try_from_fits(fname: &str) -> Result<Option<Self>> {
+        let mut fitsfile = open_fits(fname)?;
+        if fitsfile.hdu("EFIELD").is_ok() {
+            Self::from_fits(fname).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// E_theta and E_phi of the x feed followed by those of the y feed,
+    /// in the same order as `dipole::lp_ant_jones`
+    pub fn jones(&self, az: f64, pol: f64) -> [Complex<f64>; 4] {
+        let v: Vec<_> = self
+            .data
+            .iter()
+            .map(|d| natural_interp_ring(self.nside, d, SphCoord::new(pol, az)))
+            .collect();
+        [
+            Complex::new(v[0], v[1]),
+            Complex::new(v[2], v[3]),
+            Complex::new(v[4], v[5]),
+            Complex::new(v[6], v[7]),
+        ]
+    }
+
+    /// power pattern of the x feed
+    pub fn power_pattern(&self, az: f64, pol: f64) -> f64 {
+        let j = self.jones(az, pol);
+        j[0].norm_sqr() + j[1].norm_sqr()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FreqInterp {
+    Linear,
+    CubicSpline,
+}
+
+impl FromStr for FreqInterp {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(FreqInterp::Linear),
+            "cubic" => Ok(FreqInterp::CubicSpline),
+            _ => Err(format!("unknown interpolation {}, use linear or cubic", s)),
+        }
+    }
+}
+
+/// single antenna beams at a set of frequencies, interpolated pixel by pixel
+/// in between
+pub struct SingleAntCube {
+    /// sorted by frequency
+    pub beams: Vec<SingleAnt>,
+    /// only present if all beam files hold the E field
+    pub pol_beams: Option<Vec<SingleAntPol>>,
+}
+
+impl SingleAntCube {
+    pub fn new(
+        mut beams: Vec<SingleAnt>,
+        mut pol_beams: Option<Vec<SingleAntPol>>,
+    ) -> Result<Self> {
+        if beams.is_empty() {
+            return Err(Error::Invalid("no single antenna beam".to_string()));
+        }
+        if beams.iter().any(|b| b.nside != beams[0].nside) {
+            return Err(Error::Invalid(
+                "the single antenna beams have different nside".to_string(),
+            ));
+        }
+        if let Some(b) = beams.iter().find(|b| !b.freq_MHz.is_finite()) {
+            return Err(Error::Invalid(format!(
+                "single antenna beam at {} MHz",
+                b.freq_MHz
+            )));
+        }
+        beams.sort_by(|a, b| a.freq_MHz.total_cmp(&b.freq_MHz));
+        if let Some(w) = beams.windows(2).find(|w| w[0].freq_MHz == w[1].freq_MHz) {
+            return Err(Error::Invalid(format!(
+                "two single antenna beams at {} MHz",
+                w[0].freq_MHz
+            )));
+        }
+        if let Some(p) = pol_beams.as_mut() {
+            p.sort_by(|a, b| a.freq_MHz.total_cmp(&b.freq_MHz));
+        }
+        Ok(Self { beams, pol_beams })
+    }
+
+    /// one beam file per frequency
+    pub fn from_fits_files(fnames: &[String]) -> Result<Self> {
+        let beams = fnames
+            .iter()
+            .map(|f| SingleAnt::from_fits(f))
+            .collect::<Result<_>>()?;
+        let pol_beams = fnames
+            .iter()
+            .map(|f| SingleAntPol::try_from_fits(f))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .collect();
+        Self::new(beams, pol_beams)
+    }
+
+    /// every table extension with a `TEMPERATURE` column and a `FREQ_MHZ`
+    /// key is a beam at one frequency
+    pub fn from_multi_hdu_fits(fname: &str) -> Result<Self> {
+        let mut fitsfile = open_fits(fname)?;
+        let mut beams = Vec::new();
+        let mut ihdu = 1;
+        while let Ok(hdu) = fitsfile.hdu(ihdu) {
+            if has_column(&hdu, "TEMPERATURE") {
+                let freq_MHz = read_freq_key(&mut fitsfile, &hdu, "FREQ_MHZ", fname)?;
+                let data = read_temperature(fname, ihdu)?;
+                beams.push(SingleAnt::new(data, freq_MHz));
+            }
+            ihdu += 1;
+        }
+        if beams.is_empty() {
+            return Err(Error::MissingColumn {
+                path: fname.to_string(),
+                column: "TEMPERATURE".to_string(),
+            });
+        }
+        Self::new(beams, None)
+    }
+
+    /// a single file is read as a multi-HDU cube
+    pub fn from_fits(fnames: &[String]) -> Result<Self> {
+        if fnames.len() == 1 {
+            let cube = Self::from_multi_hdu_fits(&fnames[0])?;
+            if cube.beams.len() > 1 {
+                return Ok(cube);
+            }
+        }
+        Self::from_fits_files(fnames)
+    }
+
+    pub fn freqs_MHz(&self) -> Vec<f64> {
+        self.beams.iter().map(|b| b.freq_MHz).collect()
+    }
+
+    /// fails if freq_MHz is out of the range of the beams
+    pub fn power_at_freq(&self, freq_MHz: f64, interp: FreqInterp) -> Result<SingleAnt> {
+        let w = interp_weights(&self.freqs_MHz(), freq_MHz, interp)?;
+        let maps: Vec<_> = self.beams.iter().map(|b| &b.data[..]).collect();
+        Ok(SingleAnt::new(combine_maps(&maps, &w), freq_MHz))
+    }
+
+    /// None if the beams hold no E field; fails if freq_MHz is out of the
+    /// range of the beams
+    pub fn pol_at_freq(&self, freq_MHz: f64, interp: FreqInterp) -> Result<Option<SingleAntPol>> {
+        let Some(pol_beams) = self.pol_beams.as_ref() else {
+            return Ok(None);
+        };
+        let w = interp_weights(&self.freqs_MHz(), freq_MHz, interp)?;
+        let data = (0..EFIELD_COLUMNS.len())
+            .map(|i| {
+                let maps: Vec<_> = pol_beams.iter().map(|b| &b.data[i][..]).collect();
+                combine_maps(&maps, &w)
+            })
+            .collect();
+        SingleAntPol::new(data, freq_MHz).map(Some)
+    }
+}
+
+/// pixel by pixel weighted sum of maps
+pub fn combine_maps(maps: &[&[f64]], weights: &[f64]) -> Vec<f64> {
+    let mut result = vec![0.0; maps[0].len()];
+    for (m, &w) in maps.iter().zip(weights.iter()) {
+        if w != 0.0 {
+            result
+                .iter_mut()
+                .zip(m.iter())
+                .for_each(|(r, &x)| *r += w * x);
+        }
+    }
+    result
+}
+
+/// weights of the samples at the (sorted) frequencies `xs` in MHz, whose sum
+/// gives the interpolated value at x; fails if x is out of their range
+pub fn interp_weights(xs: &[f64], x: f64, interp: FreqInterp) -> Result<Vec<f64>> {
+    let n = xs.len();
+    let eps = 1e-9 * (xs[n - 1] - xs[0]).abs().max(1.0);
+    if !(x >= xs[0] - eps && x <= xs[n - 1] + eps) {
+        return Err(Error::Invalid(format!(
+            "{} MHz is out of the range [{}, {}] MHz of the beams or maps",
+            x,
+            xs[0],
+            xs[n - 1]
+        )));
+    }
+    let mut w = vec![0.0; n];
+    if let Some(i) = xs.iter().position(|&xi| (xi - x).abs() <= eps) {
+        w[i] = 1.0;
+        return Ok(w);
+    }
+    let k = xs.windows(2).position(|p| x >= p[0] && x <= p[1]).unwrap();
+    let h = xs[k + 1] - xs[k];
+    let a = (xs[k + 1] - x) / h;
+    let b = (x - xs[k]) / h;
+
+    match interp {
+        FreqInterp::CubicSpline if n > 2 => {
+            // natural cubic spline, the spline is linear in the samples, so
+            // the weights are its values for unit samples
+            for (j, wj) in w.iter_mut().enumerate() {
+                let mut y = vec![0.0; n];
+                y[j] = 1.0;
+                let m = natural_spline_second_derivatives(xs, &y);
+                *wj = a * y[k]
+                    + b * y[k + 1]
+                    + ((a * a * a - a) * m[k] + (b * b * b - b) * m[k + 1]) * h * h / 6.0;
+            }
+        }
+        _ => {
+            w[k] = a;
+            w[k + 1] = b;
+        }
+    }
+    Ok(w)
+}
+
+fn natural_spline_second_derivatives(xs: &[f64], ys: &[f64]) -> Vec<f64> {
+    let n = xs.len();
+    let mut m = vec![0.0; n];
+    // Thomas algorithm for the interior points
+    let mut c_prime = vec![0.0; n];
+    let mut d_prime = vec![0.0; n];
+    for i in 1..n - 1 {
+        let h0 = xs[i] - xs[i - 1];
+        let h1 = xs[i + 1] - xs[i];
+        let a = h0;
+        let b = 2.0 * (h0 + h1);
+        let c = h1;
+        let d = 6.0 * ((ys[i + 1] - ys[i]) / h1 - (ys[i] - ys[i - 1]) / h0);
+        let denom = b - a * c_prime[i - 1];
+        c_prime[i] = c / denom;
+        d_prime[i] = (d - a * d_prime[i - 1]) / denom;
+    }
+    for i in (1..n - 1).rev() {
+        m[i] = d_prime[i] - c_prime[i] * m[i + 1];
+    }
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KNOTS: [f64; 5] = [50.0, 60.0, 75.0, 80.0, 100.0];
+
+    #[test]
+    fn weights_sum_to_one_and_reproduce_the_knots() {
+        for interp in [FreqInterp::Linear, FreqInterp::CubicSpline] {
+            for (i, &x) in KNOTS.iter().enumerate() {
+                let w = interp_weights(&KNOTS, x, interp).unwrap();
+                let mut expected = [0.0; KNOTS.len()];
+                expected[i] = 1.0;
+                assert_eq!(w, expected);
+            }
+            for k in 0..=100 {
+                let x = 50.0 + 0.5 * k as f64;
+                let w = interp_weights(&KNOTS, x, interp).unwrap();
+                assert!((w.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+                // both reproduce straight lines
+                let line: f64 = w.iter().zip(KNOTS).map(|(w, f)| w * (3.0 * f - 2.0)).sum();
+                assert!((line - (3.0 * x - 2.0)).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn linear_weights_use_the_neighbouring_knots() {
+        let w = interp_weights(&KNOTS, 76.0, FreqInterp::Linear).unwrap();
+        assert_eq!(w, [0.0, 0.0, 0.8, 0.2, 0.0]);
+        // a spline through two knots is a line
+        let w = interp_weights(&KNOTS[..2], 52.5, FreqInterp::CubicSpline).unwrap();
+        assert_eq!(w, [0.75, 0.25]);
+    }
+
+    #[test]
+    fn out_of_range_is_an_error() {
+        for interp in [FreqInterp::Linear, FreqInterp::CubicSpline] {
+            for x in [49.0, 100.5, f64::NAN] {
+                assert!(interp_weights(&KNOTS, x, interp).is_err());
+            }
+            // the range is closed, with some tolerance
+            assert!(interp_weights(&KNOTS, 100.0 + 1e-12, interp).is_ok());
+        }
+    }
+
+    #[test]
+    fn invalid_beams_are_errors() {
+        assert!(SingleAntPol::new(vec![vec![0.0; 12]; 7], 50.0).is_err());
+        let mut data = vec![vec![0.0; 12]; 8];
+        data[3].pop();
+        assert!(SingleAntPol::new(data, 50.0).is_err());
+
+        let beam = |freq_MHz| SingleAnt {
+            data: vec![1.0; 12],
+            nside: 1,
+            freq_MHz,
+        };
+        assert!(SingleAntCube::new(vec![beam(50.0), beam(f64::NAN)], None).is_err());
+        assert!(SingleAntCube::new(vec![beam(50.0), beam(60.0), beam(50.0)], None).is_err());
+        let cube = SingleAntCube::new(vec![beam(60.0), beam(50.0)], None).unwrap();
+        assert_eq!(cube.freqs_MHz(), [50.0, 60.0]);
+    }
+}
@@ -0,0 +1,245 @@
+//! Celestial frames and time.
+//!
+//! Sky directions are unit vectors in the equatorial (J2000, precession and
+//! nutation ignored) or Galactic frame; station directions are unit vectors in
+//! the local east-north-up frame (see `coordinates`).
+
+use std::{f64::consts::PI, str::FromStr};
+
+use scorus::coordinates::Vec3d;
+
+use crate::cfg::SiteCfg;
+
+/// rows are the Galactic axes expressed in the equatorial frame
+const EQ_TO_GAL: [[f64; 3]; 3] = [
+    [-0.054875560416215, -0.873437090234885, -0.483835015548713],
+    [0.494109427875584, -0.444829629960011, 0.746982244497219],
+    [-0.867666149019005, -0.198076373431202, 0.455983776175067],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SkyFrame {
+    Equatorial,
+    Galactic,
+}
+
+impl FromStr for SkyFrame {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eq" => Ok(SkyFrame::Equatorial),
+            "gal" => Ok(SkyFrame::Galactic),
+            _ => Err(format!("unknown frame {}, use eq or gal", s)),
+        }
+    }
+}
+
+impl SkyFrame {
+    /// value of the healpix COORDSYS keyword
+    pub fn coordsys(&self) -> &'static str {
+        match self {
+            SkyFrame::Equatorial => "C",
+            SkyFrame::Galactic => "G",
+        }
+    }
+
+    pub fn from_coordsys(s: &str) -> Option<Self> {
+        match s.trim() {
+            "C" | "Q" => Some(SkyFrame::Equatorial),
+            "G" => Some(SkyFrame::Galactic),
+            _ => None,
+        }
+    }
+
+    pub fn to_eq(&self, v: &Vec3d<f64>) -> Vec3d<f64> {
+        match self {
+            SkyFrame::Equatorial => *v,
+            SkyFrame::Galactic => gal_to_eq(v),
+        }
+    }
+
+    pub fn from_eq(&self, v: &Vec3d<f64>) -> Vec3d<f64> {
+        match self {
+            SkyFrame::Equatorial => *v,
+            SkyFrame::Galactic => eq_to_gal(v),
+        }
+    }
+}
+
+pub fn eq_to_gal(v: &Vec3d<f64>) -> Vec3d<f64> {
+    let m = &EQ_TO_GAL;
+    Vec3d {
+        x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+        y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+        z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+    }
+}
+
+pub fn gal_to_eq(v: &Vec3d<f64>) -> Vec3d<f64> {
+    let m = &EQ_TO_GAL;
+    Vec3d {
+        x: m[0][0] * v.x + m[1][0] * v.y + m[2][0] * v.z,
+        y: m[0][1] * v.x + m[1][1] * v.y + m[2][1] * v.z,
+        z: m[0][2] * v.x + m[1][2] * v.y + m[2][2] * v.z,
+    }
+}
+
+/// equatorial direction seen in the east-north-up frame of a station at
+/// latitude `lat` at local sidereal time `lst` (both in rad)
+pub fn eq_to_enu(v: &Vec3d<f64>, lst: f64, lat: f64) -> Vec3d<f64> {
+    // components in the frame of the local meridian
+    let xh = v.x * lst.cos() + v.y * lst.sin();
+    let yh = -v.x * lst.sin() + v.y * lst.cos();
+    Vec3d {
+        x: yh,
+        y: -xh * lat.sin() + v.z * lat.cos(),
+        z: xh * lat.cos() + v.z * lat.sin(),
+    }
+}
+
+pub fn enu_to_eq(v: &Vec3d<f64>, lst: f64, lat: f64) -> Vec3d<f64> {
+    let xh = -v.y * lat.sin() + v.z * lat.cos();
+    let yh = v.x;
+    Vec3d {
+        x: xh * lst.cos() - yh * lst.sin(),
+        y: xh * lst.sin() + yh * lst.cos(),
+        z: v.y * lat.cos() + v.z * lat.sin(),
+    }
+}
+
+/// unit vector of (ra, dec) in rad
+pub fn radec_to_vec3d(ra: f64, dec: f64) -> Vec3d<f64> {
+    Vec3d {
+        x: dec.cos() * ra.cos(),
+        y: dec.cos() * ra.sin(),
+        z: dec.sin(),
+    }
+}
+
+/// julian date of a UTC time given as `YYYY-MM-DDThh:mm:ss[.s]`
+pub fn parse_utc(s: &str) -> Result<f64, String> {
+    let err = || format!("invalid UTC time {}, expected YYYY-MM-DDThh:mm:ss", s);
+    let (date, time) = s.split_once(['T', ' ']).ok_or_else(err)?;
+    let d: Vec<i64> = date
+        .split('-')
+        .map(|x| x.parse().map_err(|_| err()))
+        .collect::<Result<_, _>>()?;
+    let t: Vec<f64> = time
+        .split(':')
+        .map(|x| x.parse().map_err(|_| err()))
+        .collect::<Result<_, _>>()?;
+    if d.len() != 3 || t.is_empty() || t.len() > 3 {
+        return Err(err());
+    }
+    let seconds = t[0] * 3600.0 + t.get(1).unwrap_or(&0.0) * 60.0 + t.get(2).unwrap_or(&0.0);
+    Ok(julian_date(d[0], d[1], d[2]) + seconds / 86400.0)
+}
+
+/// julian date at 0h UTC of a gregorian calendar date
+pub fn julian_date(year: i64, month: i64, day: i64) -> f64 {
+    let a = (14 - month) / 12;
+    let y = year + 4800 - a;
+    let m = month + 12 * a - 3;
+    let jdn = day + (153 * m + 2) / 5 + 365 * y + y / 4 - y / 100 + y / 400 - 32045;
+    jdn as f64 - 0.5
+}
+
+/// greenwich mean sidereal time in rad
+pub fn gmst(jd: f64) -> f64 {
+    let d = jd - 2451545.0;
+    let t = d / 36525.0;
+    let deg = 280.46061837 + 360.98564736629 * d + 0.000387933 * t * t - t * t * t / 38710000.0;
+    deg.to_radians().rem_euclid(2.0 * PI)
+}
+
+impl SiteCfg {
+    /// local sidereal time in rad
+    pub fn lst(&self, jd: f64) -> f64 {
+        (gmst(jd) + self.lon_deg.to_radians()).rem_euclid(2.0 * PI)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: &Vec3d<f64>, b: &Vec3d<f64>) -> bool {
+        (a.x - b.x).abs() < 1e-12 && (a.y - b.y).abs() < 1e-12 && (a.z - b.z).abs() < 1e-12
+    }
+
+    #[test]
+    fn julian_dates_of_reference_epochs() {
+        assert_eq!(julian_date(2000, 1, 1), 2451544.5);
+        assert_eq!(parse_utc("2000-01-01T12:00:00").unwrap(), 2451545.0);
+        // MJD 0
+        assert_eq!(julian_date(1858, 11, 17), 2400000.5);
+        // Meeus, Astronomical Algorithms, example 7.a
+        assert_eq!(parse_utc("1957-10-04T19:26:24").unwrap(), 2436116.31);
+        assert!(parse_utc("2000-01-01").is_err());
+    }
+
+    #[test]
+    fn gmst_matches_meeus() {
+        let hours = |h: f64, m: f64, s: f64| (h + m / 60.0 + s / 3600.0) / 12.0 * PI;
+        // example 12.a, 1987 April 10 at 0h UT: 13h10m46.3668s
+        assert!((gmst(julian_date(1987, 4, 10)) - hours(13.0, 10.0, 46.3668)).abs() < 1e-8);
+        // example 12.b, at 19h21m UT: 8h34m57.0896s
+        let jd = parse_utc("1987-04-10T19:21:00").unwrap();
+        assert!((gmst(jd) - hours(8.0, 34.0, 57.0896)).abs() < 1e-8);
+
+        let site = SiteCfg {
+            lon_deg: 86.7,
+            lat_deg: 42.5,
+            height_m: 0.0,
+        };
+        let lst = site.lst(jd);
+        assert!((lst - (hours(8.0, 34.0, 57.0896) + 86.7_f64.to_radians())).abs() < 1e-8);
+    }
+
+    #[test]
+    fn eq_to_enu_at_the_meridian_and_the_horizon() {
+        let (lst, lat) = (1.3, 42.5_f64.to_radians());
+        // a source at dec = lat transits at the zenith
+        let zenith = eq_to_enu(&radec_to_vec3d(lst, lat), lst, lat);
+        assert!(close(
+            &zenith,
+            &Vec3d {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0
+            }
+        ));
+        // the pole is due north at an altitude equal to the latitude
+        let pole = eq_to_enu(
+            &Vec3d {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            lst,
+            lat,
+        );
+        assert!(close(
+            &pole,
+            &Vec3d {
+                x: 0.0,
+                y: lat.cos(),
+                z: lat.sin()
+            }
+        ));
+        // on the equator, 6h before transit it rises in the east
+        let east = eq_to_enu(&radec_to_vec3d(lst + PI / 2.0, 0.0), lst, lat);
+        assert!(close(
+            &east,
+            &Vec3d {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0
+            }
+        ));
+
+        let v = radec_to_vec3d(4.0, -0.3);
+        assert!(close(&enu_to_eq(&eq_to_enu(&v, lst, lat), lst, lat), &v));
+    }
+}
@@ -0,0 +1,213 @@
+//! Sky brightness temperature cubes and beam-weighted antenna temperatures.
+
+#![allow(non_snake_case)]
+
+use std::f64::consts::PI;
+
+use fitsio::hdu::HduInfo;
+use healpix_fits::read_map;
+use scorus::{
+    coordinates::Vec3d,
+    healpix::{npix2nside, utils::nside2npix},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::LIGHT_SPEED,
+    error::{open_fits, read_freq_key, Error, Result, WithPath},
+    single_ant_model::{combine_maps, interp_weights, FreqInterp},
+    sky::{radec_to_vec3d, SkyFrame},
+};
+
+const BOLTZMANN: f64 = 1.380649e-23;
+
+/// unpolarized point source with a power-law spectrum
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PointSource {
+    pub ra_deg: f64,
+    pub dec_deg: f64,
+
+    /// stokes I flux density in Jy at `ref_freq_MHz`
+    pub flux_jy: f64,
+
+    #[serde(default)]
+    pub spec_index: f64,
+
+    pub ref_freq_MHz: f64,
+}
+
+impl PointSource {
+    /// equatorial unit vector
+    pub fn dir(&self) -> Vec3d<f64> {
+        radec_to_vec3d(self.ra_deg.to_radians(), self.dec_deg.to_radians())
+    }
+
+    pub fn flux_at(&self, freq_MHz: f64) -> f64 {
+        self.flux_jy * (freq_MHz / self.ref_freq_MHz).powf(self.spec_index)
+    }
+}
+
+/// flux density in Jy of a healpix pixel with brightness temperature `t` (in
+/// K), Rayleigh-Jeans
+pub fn pixel_flux_jy(t: f64, freq_MHz: f64, nside: usize) -> f64 {
+    let freq = freq_MHz * 1e6;
+    let omega = 4.0 * PI / nside2npix(nside) as f64;
+    2.0 * BOLTZMANN * t * freq * freq / (LIGHT_SPEED * LIGHT_SPEED) * omega * 1e26
+}
+
+/// brightness temperature maps in healpix (ring ordering) at a set of frequencies
+pub struct SkyCube {
+    /// one map per frequency, sorted by frequency
+    pub maps: Vec<Vec<f64>>,
+    pub freqs_MHz: Vec<f64>,
+    pub nside: usize,
+    pub frame: SkyFrame,
+}
+
+impl SkyCube {
+    /// fails unless there is one map of the same size per (finite, distinct)
+    /// frequency
+    pub fn new(maps: Vec<Vec<f64>>, freqs_MHz: Vec<f64>, frame: SkyFrame) -> Result<Self> {
+        if maps.is_empty() || maps.len() != freqs_MHz.len() {
+            return Err(Error::Invalid(format!(
+                "{} sky maps for {} frequencies",
+                maps.len(),
+                freqs_MHz.len()
+            )));
+        }
+        if maps.iter().any(|m| m.len() != maps[0].len()) {
+            return Err(Error::Invalid(
+                "the sky maps have different sizes".to_string(),
+            ));
+        }
+        if let Some(f) = freqs_MHz.iter().find(|f| !f.is_finite()) {
+            return Err(Error::Invalid(format!("sky map at {} MHz", f)));
+        }
+        let mut pairs: Vec<_> = freqs_MHz.into_iter().zip(maps).collect();
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some(w) = pairs.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(Error::Invalid(format!("two sky maps at {} MHz", w[0].0)));
+        }
+        let nside = npix2nside(pairs[0].1.len());
+        let (freqs_MHz, maps) = pairs.into_iter().unzip();
+        Ok(Self {
+            maps,
+            freqs_MHz,
+            nside,
+            frame,
+        })
+    }
+
+    /// healpix file in the layout of `read_healpix_cube`, e.g., written by
+    /// `array-beam-sky`; the frame is taken from `COORDSYS`, equatorial if absent
+    pub fn from_fits(fname: &str) -> Result<Self> {
+        let (maps, freqs_MHz, coordsys) = read_healpix_cube(fname)?;
+        let frame = match coordsys {
+            Some(c) => SkyFrame::from_coordsys(&c)
+                .ok_or_else(|| Error::Invalid(format!("{}: unsupported COORDSYS {}", fname, c)))?,
+            None => SkyFrame::Equatorial,
+        };
+        Self::new(maps, freqs_MHz, frame).map_err(|e| Error::Invalid(format!("{}: {}", fname, e)))
+    }
+
+    /// fails if freq_MHz is out of the range of the maps
+    pub fn map_at_freq(&self, freq_MHz: f64, interp: FreqInterp) -> Result<Vec<f64>> {
+        let w = interp_weights(&self.freqs_MHz, freq_MHz, interp)?;
+        let maps: Vec<_> = self.maps.iter().map(|m| &m[..]).collect();
+        Ok(combine_maps(&maps, &w))
+    }
+}
+
+/// maps, frequencies in MHz and COORDSYS of a healpix file
+pub type HealpixCube = (Vec<Vec<f64>>, Vec<f64>, Option<String>);
+
+/// healpix file with one column per frequency and the frequencies (in Hz) in
+/// the keys `FREQ1`, `FREQ2`, ...; a single map with the `FREQ_MHZ` key (a
+/// single antenna beam) is also accepted
+pub fn read_healpix_cube(fname: &str) -> Result<HealpixCube> {
+    let mut fitsfile = open_fits(fname)?;
+    let hdu = fitsfile.hdu(1).with_path(fname)?;
+    let colnames: Vec<String> = match &hdu.info {
+        HduInfo::TableInfo {
+            column_descriptions,
+            ..
+        } if !column_descriptions.is_empty() => {
+            column_descriptions.iter().map(|c| c.name.clone()).collect()
+        }
+        _ => {
+            return Err(Error::Invalid(format!(
+                "{}: no healpix table in hdu 1",
+                fname
+            )))
+        }
+    };
+    let (colnames, freqs_MHz): (Vec<String>, Vec<f64>) =
+        match hdu.read_key::<f64>(&mut fitsfile, "FREQ_MHZ") {
+            Ok(_) if hdu.read_key::<f64>(&mut fitsfile, "FREQ1").is_err() => {
+                let f = read_freq_key(&mut fitsfile, &hdu, "FREQ_MHZ", fname)?;
+                (vec![colnames[0].clone()], vec![f])
+            }
+            _ => {
+                let freqs = (1..=colnames.len())
+                    .map(|i| {
+                        read_freq_key(&mut fitsfile, &hdu, &format!("FREQ{}", i), fname)
+                            .map(|f| f / 1e6)
+                    })
+                    .collect::<Result<_>>()?;
+                (colnames, freqs)
+            }
+        };
+    let coordsys = hdu.read_key::<String>(&mut fitsfile, "COORDSYS").ok();
+    let colnames: Vec<_> = colnames.iter().map(|c| c.as_str()).collect();
+    let maps = read_map::<f64>(fname, &colnames, 1);
+    Ok((maps, freqs_MHz, coordsys))
+}
+
+/// beam-weighted mean of the sky temperature, both maps on the same pixels;
+/// pixels where the beam is zero (e.g., below the horizon) do not contribute;
+/// fails if the beam is zero everywhere, e.g., entirely below the horizon
+pub fn antenna_temperature(beam: &[f64], sky: &[f64]) -> Result<f64> {
+    if beam.len() != sky.len() {
+        return Err(Error::Invalid(format!(
+            "beam of {} pixels on a sky map of {}",
+            beam.len(),
+            sky.len()
+        )));
+    }
+    let (bt, b) = beam
+        .iter()
+        .zip(sky.iter())
+        .fold((0.0, 0.0), |(bt, b), (&x, &t)| (bt + x * t, b + x));
+    if b > 0.0 {
+        Ok(bt / b)
+    } else {
+        Err(Error::Invalid(
+            "the beam is zero on the whole sky map".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inconsistent_maps_are_errors() {
+        let maps = vec![vec![0.0; 12]; 2];
+        let eq = SkyFrame::Equatorial;
+        assert!(SkyCube::new(maps.clone(), vec![50.0], eq).is_err());
+        assert!(SkyCube::new(Vec::new(), Vec::new(), eq).is_err());
+        assert!(SkyCube::new(maps.clone(), vec![50.0, f64::NAN], eq).is_err());
+        assert!(SkyCube::new(maps, vec![50.0, 50.0], eq).is_err());
+        assert!(SkyCube::new(vec![vec![0.0; 12], vec![0.0; 48]], vec![50.0, 60.0], eq).is_err());
+    }
+
+    #[test]
+    fn antenna_temperature_of_a_beam_below_the_horizon_is_an_error() {
+        let sky = [10.0, 20.0, 30.0, 40.0];
+        let t = antenna_temperature(&[1.0, 0.0, 3.0, 0.0], &sky).unwrap();
+        assert!((t - 25.0).abs() < 1e-12);
+        assert!(antenna_temperature(&[0.0; 4], &sky).is_err());
+        assert!(antenna_temperature(&[1.0; 3], &sky).is_err());
+    }
+}
@@ -0,0 +1,411 @@
+use std::{f64::consts::PI, str::FromStr};
+
+use crate::cfg::ArrayCfg;
+
+/// radial amplitude taper across the aperture, sidelobe levels are given in
+/// dB below the main lobe; `Taylor` is the circular (Hansen) distribution on
+/// the largest disc inside the array, `DolphChebyshev` applies the linear
+/// window along the radius and is only an approximation for planar arrays,
+/// whose sidelobes come out several dB above the design level
+#[derive(Clone, Copy, Debug)]
+pub enum Taper {
+    Uniform,
+    Gaussian { edge_db: f64 },
+    Hamming,
+    Taylor { nbar: usize, sll_db: f64 },
+    DolphChebyshev { sll_db: f64 },
+}
+
+impl FromStr for Taper {
+    type Err = String;
+
+    /// `uniform`, `hamming`, `gaussian:<edge dB>`, `taylor:<nbar>:<sll dB>`
+    /// or `chebyshev:<sll dB>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let items: Vec<_> = s.split(':').collect();
+        let num = |i: usize| -> Result<f64, String> {
+            items
+                .get(i)
+                .ok_or(format!("missing parameter in taper {}", s))?
+                .parse::<f64>()
+                .map_err(|e| format!("invalid parameter in taper {}: {}", s, e))
+        };
+        let count = |i: usize| -> Result<usize, String> {
+            items
+                .get(i)
+                .ok_or(format!("missing parameter in taper {}", s))?
+                .parse::<usize>()
+                .map_err(|e| format!("invalid parameter in taper {}: {}", s, e))
+        };
+        match items[0] {
+            "uniform" => Ok(Taper::Uniform),
+            "hamming" => Ok(Taper::Hamming),
+            "gaussian" => Ok(Taper::Gaussian { edge_db: num(1)? }),
+            "taylor" => match count(1)? {
+                0 => Err(format!("nbar must be at least 1 in taper {}", s)),
+                nbar => Ok(Taper::Taylor {
+                    nbar,
+                    sll_db: num(2)?,
+                }),
+            },
+            "chebyshev" => Ok(Taper::DolphChebyshev { sll_db: num(1)? }),
+            _ => Err(format!("unknown taper {}", s)),
+        }
+    }
+}
+
+/// Taylor line-source distribution at x, the position in units of the
+/// aperture length, in [-1/2, 1/2]
+pub fn taylor_weight(x: f64, nbar: usize, sll_db: f64) -> f64 {
+    let a = (10_f64.powf(sll_db / 20.0)).acosh() / PI;
+    let nb = nbar as f64;
+    let sigma2 = nb * nb / (a * a + (nb - 0.5).powi(2));
+    1.0 + 2.0
+        * (1..nbar)
+            .map(|m| {
+                let mf = m as f64;
+                let num = (1..nbar)
+                    .map(|n| 1.0 - mf * mf / (sigma2 * (a * a + (n as f64 - 0.5).powi(2))))
+                    .product::<f64>();
+                let den = (1..nbar)
+                    .filter(|&n| n != m)
+                    .map(|n| 1.0 - mf * mf / (n * n) as f64)
+                    .product::<f64>();
+                let sign = if m % 2 == 1 { 1.0 } else { -1.0 };
+                sign * num / (2.0 * den) * (2.0 * PI * mf * x).cos()
+            })
+            .sum::<f64>()
+}
+
+/// Bessel function of the first kind of integer order, from its integral
+/// representation, accurate for |x| up to a few tens
+fn bessel_j(order: usize, x: f64) -> f64 {
+    let n = 200;
+    (0..n)
+        .map(|i| {
+            let t = (i as f64 + 0.5) * PI / n as f64;
+            (order as f64 * t - x * t.sin()).cos()
+        })
+        .sum::<f64>()
+        / n as f64
+}
+
+/// m-th positive zero of J1 over pi, the m-th null of the uniform circular
+/// aperture in Hansen's u = 2 a sin(theta) / lambda
+fn bessel_j1_zero(m: usize) -> f64 {
+    let b = (m as f64 + 0.25) * PI;
+    let mut x = b - 3.0 / (8.0 * b);
+    for _ in 0..10 {
+        let j1 = bessel_j(1, x);
+        x -= j1 / (bessel_j(0, x) - j1 / x);
+    }
+    x / PI
+}
+
+/// circular Taylor (Hansen) distribution as `(mu_m, c_m)` pairs, the weight at
+/// rho, the radius in units of the aperture radius, is the sum of
+/// `c_m J0(pi mu_m rho)`
+pub fn circular_taylor_terms(nbar: usize, sll_db: f64) -> Vec<(f64, f64)> {
+    let a = (10_f64.powf(sll_db / 20.0)).acosh() / PI;
+    let nb = nbar as f64;
+    let mu: Vec<_> = (0..nbar)
+        .map(|m| if m == 0 { 0.0 } else { bessel_j1_zero(m) })
+        .collect();
+    let sigma = bessel_j1_zero(nbar) / (a * a + (nb - 0.5).powi(2)).sqrt();
+    let zeros: Vec<_> = (1..nbar)
+        .map(|n| sigma * (a * a + (n as f64 - 0.5).powi(2)).sqrt())
+        .collect();
+    mu.iter()
+        .enumerate()
+        .map(|(m, &mu_m)| {
+            if m == 0 {
+                return (0.0, 1.0);
+            }
+            let j0 = bessel_j(0, PI * mu_m);
+            let num = zeros
+                .iter()
+                .map(|&u| 1.0 - mu_m * mu_m / (u * u))
+                .product::<f64>();
+            let den = (1..nbar)
+                .filter(|&n| n != m)
+                .map(|n| 1.0 - mu_m * mu_m / (mu[n] * mu[n]))
+                .product::<f64>();
+            (mu_m, -num / (den * j0))
+        })
+        .collect()
+}
+
+fn chebyshev_poly(order: usize, x: f64) -> f64 {
+    let n = order as f64;
+    if x.abs() <= 1.0 {
+        (n * x.acos()).cos()
+    } else if x > 1.0 {
+        (n * x.acosh()).cosh()
+    } else {
+        let sign = if order.is_multiple_of(2) { 1.0 } else { -1.0 };
+        sign * (n * (-x).acosh()).cosh()
+    }
+}
+
+/// the centre and one half of a `2m+1` point Dolph-Chebyshev window,
+/// normalized to unit peak
+pub fn chebyshev_half_window(m: usize, sll_db: f64) -> Vec<f64> {
+    let n = 2 * m + 1;
+    let order = n - 1;
+    let beta = ((10_f64.powf(sll_db / 20.0)).acosh() / order as f64).cosh();
+    let p: Vec<_> = (0..n)
+        .map(|k| chebyshev_poly(order, beta * (PI * k as f64 / n as f64).cos()))
+        .collect();
+    let w: Vec<_> = (0..=m)
+        .map(|j| {
+            p.iter()
+                .enumerate()
+                .map(|(k, &pk)| pk * (2.0 * PI * (j * k) as f64 / n as f64).cos())
+                .sum::<f64>()
+        })
+        .collect();
+    let wmax = w.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    w.iter().map(|&x| x / wmax).collect()
+}
+
+/// amplitude weights of the antennas in `cfg` (flagged ones get zero),
+/// the taper is a function of the horizontal distance to the centroid of the
+/// working antennas
+pub fn calc_taper_weights(cfg: &ArrayCfg, taper: &Taper) -> Vec<f64> {
+    let (ant_x, ant_y, _) = cfg.positions();
+    let w0 = cfg.weights();
+    let nvalid = w0.iter().filter(|&&w| w > 0.0).count().max(1) as f64;
+    let (xc, yc) = ant_x
+        .iter()
+        .zip(ant_y.iter().zip(w0.iter()))
+        .filter(|(_, (_, &w))| w > 0.0)
+        .fold((0.0, 0.0), |(sx, sy), (&x, (&y, _))| {
+            (sx + x / nvalid, sy + y / nvalid)
+        });
+    let r: Vec<_> = ant_x
+        .iter()
+        .zip(ant_y.iter())
+        .map(|(&x, &y)| (x - xc).hypot(y - yc))
+        .collect();
+    let rmax = r
+        .iter()
+        .zip(w0.iter())
+        .filter(|(_, &w)| w > 0.0)
+        .map(|(&r, _)| r)
+        .fold(0.0, f64::max);
+
+    let radial: Box<dyn Fn(f64) -> f64> = match *taper {
+        Taper::Uniform => Box::new(|_| 1.0),
+        Taper::Gaussian { edge_db } => {
+            let a = edge_db / 20.0 * 10_f64.ln();
+            Box::new(move |rho| (-a * rho * rho).exp())
+        }
+        Taper::Hamming => Box::new(|rho| 0.54 + 0.46 * (PI * rho).cos()),
+        Taper::Taylor { nbar, sll_db } => {
+            let terms = circular_taylor_terms(nbar, sll_db);
+            let weight = move |rho: f64| {
+                terms
+                    .iter()
+                    .map(|&(mu, c)| c * bessel_j(0, PI * mu * rho))
+                    .sum::<f64>()
+            };
+            let w_center = weight(0.0);
+            // the distribution is defined on a disc, take the largest one
+            // about the centroid inside the footprint of the working antennas
+            // and switch off the antennas outside it
+            let edge = if rmax > 0.0 {
+                (inscribed_radius(&ant_x, &ant_y, &w0, xc, yc)
+                    + 0.5 * mean_nearest_distance(&ant_x, &ant_y))
+                    / rmax
+            } else {
+                1.0
+            };
+            Box::new(move |rho| {
+                if rho > edge {
+                    0.0
+                } else {
+                    weight(rho / edge) / w_center
+                }
+            })
+        }
+        Taper::DolphChebyshev { sll_db } => {
+            // one window sample per antenna along a radius, the linear window
+            // does not give equal sidelobes in the plane, so this is only an
+            // approximation
+            let spacing = mean_nearest_distance(&ant_x, &ant_y);
+            let m = if spacing > 0.0 {
+                (rmax / spacing).round().max(1.0) as usize
+            } else {
+                1
+            };
+            let w = chebyshev_half_window(m, sll_db);
+            Box::new(move |rho| {
+                let t = rho.clamp(0.0, 1.0) * m as f64;
+                let i = (t.floor() as usize).min(m - 1);
+                let f = t - i as f64;
+                w[i] * (1.0 - f) + w[i + 1] * f
+            })
+        }
+    };
+
+    r.iter()
+        .zip(w0.iter())
+        .map(|(&r, &w)| {
+            let rho = if rmax > 0.0 { r / rmax } else { 0.0 };
+            w * radial(rho)
+        })
+        .collect()
+}
+
+/// radius of the largest disc about (xc, yc) inside the convex hull of the
+/// working antennas, the minimum of the hull's support function
+fn inscribed_radius(x: &[f64], y: &[f64], w: &[f64], xc: f64, yc: f64) -> f64 {
+    (0..360)
+        .map(|i| {
+            let (s, c) = (i as f64).to_radians().sin_cos();
+            x.iter()
+                .zip(y.iter().zip(w.iter()))
+                .filter(|(_, (_, &w))| w > 0.0)
+                .map(|(&x, (&y, _))| (x - xc) * c + (y - yc) * s)
+                .fold(0.0, f64::max)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn mean_nearest_distance(x: &[f64], y: &[f64]) -> f64 {
+    if x.len() < 2 {
+        return 0.0;
+    }
+    x.iter()
+        .zip(y.iter())
+        .enumerate()
+        .map(|(i, (&x1, &y1))| {
+            x.iter()
+                .zip(y.iter())
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, (&x2, &y2))| (x1 - x2).hypot(y1 - y2))
+                .fold(f64::INFINITY, f64::min)
+        })
+        .sum::<f64>()
+        / x.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{layout_cfg, Layout};
+
+    #[test]
+    fn chebyshev_matches_the_five_element_20db_array() {
+        // currents 1 : 1.6085 : 1.9319 (Stutzman & Thiele)
+        let w = chebyshev_half_window(2, 20.0);
+        for (a, b) in w.iter().zip([1.0, 1.6085 / 1.9319, 1.0 / 1.9319]) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    /// pattern in dB of the Taylor line source, u in units of the
+    /// reciprocal aperture length
+    fn taylor_pattern(nbar: usize, sll_db: f64) -> impl Fn(f64) -> f64 {
+        let n = 2000;
+        let xs: Vec<_> = (0..n).map(|i| -0.5 + (i as f64 + 0.5) / n as f64).collect();
+        let ws: Vec<_> = xs.iter().map(|&x| taylor_weight(x, nbar, sll_db)).collect();
+        let f = move |u: f64| {
+            xs.iter()
+                .zip(&ws)
+                .map(|(&x, &w)| w * (2.0 * PI * u * x).cos())
+                .sum::<f64>()
+        };
+        let f0 = f(0.0);
+        move |u| 20.0 * (f(u) / f0).abs().log10()
+    }
+
+    #[test]
+    fn taylor_sidelobes_are_at_the_design_level() {
+        for (nbar, sll_db) in [(4, 30.0), (5, 25.0), (6, 35.0)] {
+            let pattern = taylor_pattern(nbar, sll_db);
+            let a = (10_f64.powf(sll_db / 20.0)).acosh() / PI;
+            let nb = nbar as f64;
+            let sigma = nb / (a * a + (nb - 0.5).powi(2)).sqrt();
+            // the first nbar - 1 nulls are those of the ideal pattern dilated
+            // by sigma, the first sidelobe is close to the design level
+            for n in 1..nbar {
+                let u = sigma * (a * a + (n as f64 - 0.5).powi(2)).sqrt();
+                assert!(pattern(u) < -80.0);
+            }
+            let first_null = sigma * (a * a + 0.25).sqrt();
+            let second_null = sigma * (a * a + 2.25).sqrt();
+            let peak = (0..=100)
+                .map(|i| pattern(first_null + (second_null - first_null) * i as f64 / 100.0))
+                .fold(f64::NEG_INFINITY, f64::max);
+            assert!((peak + sll_db).abs() < 0.5);
+        }
+    }
+
+    /// first sidelobe in dB of the array factor of the tapered `hex:1:10`
+    /// layout along east, u in cycles per unit length
+    fn hex_first_sidelobe(taper: Taper) -> f64 {
+        let cfg = layout_cfg(
+            &"hex:1:10".parse::<Layout>().unwrap().positions().unwrap(),
+            None,
+        );
+        let (x, _, _) = cfg.positions();
+        let w = calc_taper_weights(&cfg, &taper);
+        let af = |u: f64| {
+            x.iter()
+                .zip(&w)
+                .map(|(&x, &w)| w * (2.0 * PI * u * x).cos())
+                .sum::<f64>()
+                .abs()
+        };
+        let af0 = af(0.0);
+        let du = 1e-4;
+        let mut u = du;
+        while af(u + du) < af(u) {
+            u += du;
+        }
+        while af(u + du) > af(u) {
+            u += du;
+        }
+        20.0 * (af(u) / af0).log10()
+    }
+
+    #[test]
+    fn taylor_sidelobes_of_a_hex_array_are_at_the_design_level() {
+        assert!((hex_first_sidelobe(Taper::Uniform) + 16.6).abs() < 0.5);
+        for (nbar, sll_db) in [(4, 30.0), (5, 25.0)] {
+            let sll = hex_first_sidelobe(Taper::Taylor { nbar, sll_db });
+            assert!((sll + sll_db).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn taylor_nbar_is_a_positive_integer() {
+        assert!(matches!(
+            "taylor:4:30".parse::<Taper>(),
+            Ok(Taper::Taylor { nbar: 4, .. })
+        ));
+        for s in ["taylor:4.5:30", "taylor:-3:30", "taylor:0:30"] {
+            assert!(s.parse::<Taper>().is_err());
+        }
+    }
+
+    #[test]
+    fn gaussian_and_hamming_reach_their_edge_values() {
+        let cfg = layout_cfg(
+            &"hex:2:3".parse::<Layout>().unwrap().positions().unwrap(),
+            None,
+        );
+        let extremes = |taper| {
+            let w = calc_taper_weights(&cfg, &taper);
+            let max = w.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let min = w.iter().cloned().fold(f64::INFINITY, f64::min);
+            (max, min)
+        };
+        let (max, min) = extremes(Taper::Gaussian { edge_db: 12.0 });
+        assert!((max - 1.0).abs() < 1e-12 && (min - 10_f64.powf(-0.6)).abs() < 1e-12);
+        let (max, min) = extremes(Taper::Hamming);
+        assert!((max - 1.0).abs() < 1e-12 && (min - 0.08).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,373 @@
+//! Checks of the geometry of an array cfg: duplicated or overlapping
+//! antennas, baselines, centroid, planarity and snapshot uv coverage.
+
+use std::{collections::HashSet, f64::consts::FRAC_1_SQRT_2};
+
+use serde::Serialize;
+
+use crate::cfg::{AntCfg, ArrayCfg, ElementCfg};
+
+/// antennas closer than this (in m) are taken as the same antenna
+pub const DUPLICATE_TOL: f64 = 1e-3;
+
+#[derive(Serialize)]
+pub struct Diagnostics {
+    pub n_ants: usize,
+    pub n_flagged: usize,
+    /// problems making the cfg unusable
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    /// pairs of antennas (0-based, in the order of `ArrayCfg::all_ants`) at
+    /// the same position
+    pub duplicates: Vec<(usize, usize)>,
+    /// pairs of antennas closer than the element size, with their distance
+    pub overlaps: Vec<(usize, usize, f64)>,
+    /// horizontal baselines between unflagged antennas, in m
+    pub min_baseline_m: f64,
+    pub max_baseline_m: f64,
+    /// mean position of the unflagged antennas, relative to the reference
+    /// point of the cfg
+    pub centroid_m: (f64, f64, f64),
+    /// horizontal distance of the centroid from the reference point
+    pub centroid_offset_m: f64,
+    pub max_abs_z_m: f64,
+    /// rms distance from the best fitting plane and its tilt from the
+    /// horizontal, None for fewer than three antennas or collinear antennas
+    pub plane_rms_m: Option<f64>,
+    pub plane_tilt_deg: Option<f64>,
+    pub uv: UvCoverage,
+}
+
+/// coverage of the uv plane by the baselines of a zenith snapshot, on cells
+/// of `cell_m`
+#[derive(Serialize)]
+pub struct UvCoverage {
+    pub cell_m: f64,
+    pub n_baselines: usize,
+    /// baselines falling in the same cell count once
+    pub n_unique: usize,
+    pub redundancy: f64,
+    /// fraction of the cells within the longest baseline (plus half the
+    /// diagonal of a cell, the largest rounding to a cell) hit by a baseline
+    pub filling_factor: f64,
+}
+
+/// problems that make any use of the antennas meaningless: non-finite
+/// positions, weights or orientations, negative weights, duplicates, no
+/// unflagged antenna, element beams missing from the cfg
+pub fn hard_errors(cfg: &ArrayCfg) -> Vec<String> {
+    let ants = cfg.all_ants();
+    let mut errors = Vec::new();
+    for (i, a) in ants.iter().enumerate() {
+        let (x, y, z) = a.pos;
+        if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+            errors.push(format!("antenna {} has a non-finite position", i));
+        }
+        let g = &a.gain;
+        if !(g.weight.is_finite() && g.phase_deg.is_finite() && g.delay_ns.is_finite()) {
+            errors.push(format!("antenna {} has a non-finite gain", i));
+        } else if g.weight < 0.0 {
+            errors.push(format!("antenna {} has a negative weight", i));
+        }
+        if !a.orientation_deg.is_finite() {
+            errors.push(format!("antenna {} has a non-finite orientation", i));
+        }
+        if let Some(e) = &a.element {
+            if !cfg.elements.contains_key(e) {
+                errors.push(format!("antenna {} refers to the unknown element {}", i, e));
+            }
+        }
+    }
+    for (name, e) in &cfg.elements {
+        match e {
+            ElementCfg::Beam { files } if files.is_empty() => {
+                errors.push(format!("element {} has no beam file", name))
+            }
+            ElementCfg::Dipole { length_m } if !length_m.is_finite() || *length_m <= 0.0 => {
+                errors.push(format!("element {} has a non-positive length", name))
+            }
+            _ => {}
+        }
+    }
+    for (i, j) in duplicates(&ants) {
+        errors.push(format!("antennas {} and {} are at the same position", i, j));
+    }
+    if ants.iter().all(|a| a.gain.effective_weight() == 0.0) {
+        errors.push("no unflagged antenna with a non-zero weight".to_string());
+    }
+    errors
+}
+
+fn distance(a: &AntCfg, b: &AntCfg) -> f64 {
+    let (dx, dy, dz) = (a.pos.0 - b.pos.0, a.pos.1 - b.pos.1, a.pos.2 - b.pos.2);
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
+
+fn duplicates(ants: &[AntCfg]) -> Vec<(usize, usize)> {
+    pairs(ants.len())
+        .filter(|&(i, j)| distance(&ants[i], &ants[j]) < DUPLICATE_TOL)
+        .collect()
+}
+
+fn pairs(n: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..n).flat_map(move |i| (i + 1..n).map(move |j| (i, j)))
+}
+
+/// rms residual and tilt (in rad) of the least-squares plane z = a x + b y + c
+fn fit_plane(pos: &[(f64, f64, f64)]) -> Option<(f64, f64)> {
+    let n = pos.len() as f64;
+    if pos.len() < 3 {
+        return None;
+    }
+    let (mx, my, mz) = pos.iter().fold((0.0, 0.0, 0.0), |s, p| {
+        (s.0 + p.0 / n, s.1 + p.1 / n, s.2 + p.2 / n)
+    });
+    let (mut sxx, mut sxy, mut syy, mut sxz, mut syz) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for &(x, y, z) in pos {
+        let (x, y, z) = (x - mx, y - my, z - mz);
+        sxx += x * x;
+        sxy += x * y;
+        syy += y * y;
+        sxz += x * z;
+        syz += y * z;
+    }
+    let det = sxx * syy - sxy * sxy;
+    if det <= 1e-12 * (sxx + syy).powi(2) {
+        return None;
+    }
+    let a = (sxz * syy - syz * sxy) / det;
+    let b = (syz * sxx - sxz * sxy) / det;
+    let rms = (pos
+        .iter()
+        .map(|&(x, y, z)| (z - mz - a * (x - mx) - b * (y - my)).powi(2))
+        .sum::<f64>()
+        / n)
+        .sqrt();
+    // distance from the plane rather than vertical residual
+    Some((rms / (1.0 + a * a + b * b).sqrt(), a.hypot(b).atan()))
+}
+
+/// `element_size` (in m) is the smallest allowed distance between antennas
+/// and the uv cell; with `flat`, antennas off the z = 0 plane are errors
+pub fn validate(cfg: &ArrayCfg, element_size: f64, flat: bool) -> Diagnostics {
+    let ants = cfg.all_ants();
+    let mut errors = hard_errors(cfg);
+    let mut warnings = Vec::new();
+
+    let dups = duplicates(&ants);
+    let overlaps: Vec<_> = pairs(ants.len())
+        .map(|(i, j)| (i, j, distance(&ants[i], &ants[j])))
+        .filter(|&(_, _, d)| d >= DUPLICATE_TOL && d < element_size)
+        .collect();
+    for &(i, j, d) in &overlaps {
+        errors.push(format!(
+            "antennas {} and {} are {:.3} m apart, closer than the element size",
+            i, j, d
+        ));
+    }
+
+    let active: Vec<_> = ants
+        .iter()
+        .filter(|a| a.gain.effective_weight() > 0.0)
+        .map(|a| a.pos)
+        .collect();
+    let n_flagged = ants.len() - active.len();
+    if n_flagged > 0 {
+        warnings.push(format!("{} flagged or zero-weight antennas", n_flagged));
+    }
+
+    let max_abs_z_m = ants.iter().map(|a| a.pos.2.abs()).fold(0.0, f64::max);
+    if max_abs_z_m > DUPLICATE_TOL {
+        let msg = format!("antennas up to {} m off the z = 0 plane", max_abs_z_m);
+        if flat {
+            errors.push(msg);
+        } else {
+            warnings.push(msg);
+        }
+    }
+
+    let n = active.len().max(1) as f64;
+    let centroid_m = active.iter().fold((0.0, 0.0, 0.0), |s, p| {
+        (s.0 + p.0 / n, s.1 + p.1 / n, s.2 + p.2 / n)
+    });
+    let centroid_offset_m = centroid_m.0.hypot(centroid_m.1);
+    if centroid_offset_m > element_size {
+        warnings.push(format!(
+            "the centroid is {:.3} m off the reference point",
+            centroid_offset_m
+        ));
+    }
+    let plane = fit_plane(&active);
+
+    let baselines: Vec<_> = pairs(active.len())
+        .map(|(i, j)| (active[j].0 - active[i].0, active[j].1 - active[i].1))
+        .collect();
+    let lengths = baselines.iter().map(|b| b.0.hypot(b.1));
+    let min_baseline_m = lengths.clone().fold(f64::INFINITY, f64::min);
+    let max_baseline_m = lengths.fold(0.0, f64::max);
+
+    Diagnostics {
+        n_ants: ants.len(),
+        n_flagged,
+        errors,
+        warnings,
+        duplicates: dups,
+        overlaps,
+        min_baseline_m,
+        max_baseline_m,
+        centroid_m,
+        centroid_offset_m,
+        max_abs_z_m,
+        plane_rms_m: plane.map(|p| p.0),
+        plane_tilt_deg: plane.map(|p| p.1.to_degrees()),
+        uv: uv_coverage(&baselines, max_baseline_m, element_size),
+    }
+}
+
+fn uv_cell(b: (f64, f64), cell: f64) -> (i64, i64) {
+    ((b.0 / cell).round() as i64, (b.1 / cell).round() as i64)
+}
+
+/// number of cells (i, j) with i^2 + j^2 <= r^2, counted per row
+fn disc_cells(r: f64) -> usize {
+    let n = r.floor() as i64;
+    (-n..=n)
+        .map(|i| 2 * (r * r - (i * i) as f64).max(0.0).sqrt().floor() as usize + 1)
+        .sum()
+}
+
+fn uv_coverage(baselines: &[(f64, f64)], max_baseline: f64, cell: f64) -> UvCoverage {
+    let cells: HashSet<_> = baselines
+        .iter()
+        .flat_map(|&(u, v)| [uv_cell((u, v), cell), uv_cell((-u, -v), cell)])
+        .collect();
+    // each baseline and its conjugate, except for a cell at the origin
+    let n_unique = cells.iter().filter(|c| **c > (0, 0)).count();
+    let n_cells = disc_cells(max_baseline / cell + FRAC_1_SQRT_2);
+    UvCoverage {
+        cell_m: cell,
+        n_baselines: baselines.len(),
+        n_unique,
+        redundancy: baselines.len() as f64 / n_unique.max(1) as f64,
+        filling_factor: cells.len() as f64 / n_cells as f64,
+    }
+}
+
+/// number of baselines (each with its conjugate) per uv cell of a zenith
+/// snapshot, on an `n` by `n` grid centred on the origin, u along the rows
+pub fn uv_grid(cfg: &ArrayCfg, cell: f64, n: usize) -> Vec<f64> {
+    let pos: Vec<_> = cfg
+        .all_ants()
+        .iter()
+        .filter(|a| a.gain.effective_weight() > 0.0)
+        .map(|a| a.pos)
+        .collect();
+    let mut grid = vec![0.0; n * n];
+    let c = (n / 2) as i64;
+    for (i, j) in pairs(pos.len()) {
+        let (u, v) = (pos[j].0 - pos[i].0, pos[j].1 - pos[i].1);
+        for (iu, iv) in [uv_cell((u, v), cell), uv_cell((-u, -v), cell)] {
+            let (x, y) = (iu + c, iv + c);
+            if (0..n as i64).contains(&x) && (0..n as i64).contains(&y) {
+                grid[y as usize * n + x as usize] += 1.0;
+            }
+        }
+    }
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::layout_cfg;
+
+    fn cfg(pos: &[(f64, f64, f64)]) -> ArrayCfg {
+        let mut cfg = layout_cfg(&[], None);
+        cfg.ants = pos.iter().map(|&p| AntCfg::new(p)).collect();
+        cfg
+    }
+
+    #[test]
+    fn duplicates_and_overlaps() {
+        let c = cfg(&[
+            (0.0, 0.0, 0.0),
+            (1e-4, 0.0, 0.0),
+            (0.5, 0.0, 0.0),
+            (3.0, 0.0, 0.0),
+        ]);
+        assert_eq!(hard_errors(&c).len(), 1);
+        let d = validate(&c, 1.0, false);
+        assert_eq!(d.duplicates, [(0, 1)]);
+        let overlaps: Vec<_> = d.overlaps.iter().map(|&(i, j, _)| (i, j)).collect();
+        assert_eq!(overlaps, [(0, 2), (1, 2)]);
+        assert_eq!(d.errors.len(), 3);
+        assert!((d.min_baseline_m - 1e-4).abs() < 1e-12);
+        assert_eq!(d.max_baseline_m, 3.0);
+    }
+
+    #[test]
+    fn flagged_and_invalid_antennas() {
+        let mut c = cfg(&[(0.0, 0.0, 0.0), (2.0, 0.0, 0.0), (f64::NAN, 0.0, 0.0)]);
+        c.ants[1].gain.flagged = true;
+        c.ants[0].gain.weight = -1.0;
+        let errors = hard_errors(&c);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        c.ants[2].pos.0 = 4.0;
+        c.ants[0].gain.weight = 1.0;
+        let d = validate(&c, 1.0, false);
+        assert!(d.errors.is_empty());
+        assert_eq!(d.n_flagged, 1);
+        assert_eq!(d.centroid_m, (2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn tilted_plane() {
+        let pos: Vec<_> = [(0.0, 0.0), (4.0, 0.0), (0.0, 4.0), (4.0, 4.0), (2.0, 1.0)]
+            .iter()
+            .map(|&(x, y)| (x, y, 0.1 * x - 0.05 * y))
+            .collect();
+        let d = validate(&cfg(&pos), 1.0, false);
+        assert!(d.plane_rms_m.unwrap() < 1e-12);
+        let tilt = 0.1f64.hypot(0.05).atan().to_degrees();
+        assert!((d.plane_tilt_deg.unwrap() - tilt).abs() < 1e-9);
+        assert!(!d.warnings.is_empty());
+        assert!(!validate(&cfg(&pos), 1.0, true).errors.is_empty());
+
+        let line = cfg(&[(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (2.0, 0.0, 0.0)]);
+        assert!(validate(&line, 0.5, false).plane_rms_m.is_none());
+    }
+
+    #[test]
+    fn uv_counts() {
+        let c = cfg(&[
+            (0.0, 0.0, 0.0),
+            (1.0, 0.0, 0.0),
+            (0.0, 1.0, 0.0),
+            (1.0, 1.0, 0.0),
+        ]);
+        let uv = validate(&c, 1.0, false).uv;
+        assert_eq!(uv.n_baselines, 6);
+        // (1, 0) and (0, 1) twice, the two diagonals once
+        assert_eq!(uv.n_unique, 4);
+        assert_eq!(uv.redundancy, 1.5);
+        // 8 cells hit out of the 13 within sqrt(2) + sqrt(1/2)
+        assert!((uv.filling_factor - 8.0 / 13.0).abs() < 1e-12);
+
+        let grid = uv_grid(&c, 1.0, 5);
+        assert_eq!(grid.iter().sum::<f64>(), 12.0);
+        assert_eq!(grid[2 * 5 + 3], 2.0);
+        assert_eq!(grid[3 * 5 + 3], 1.0);
+        assert_eq!(grid[2 * 5 + 2], 0.0);
+    }
+
+    #[test]
+    fn disc_cell_count() {
+        assert_eq!(disc_cells(0.5), 1);
+        assert_eq!(disc_cells(1.0), 5);
+        assert_eq!(disc_cells(2.0), 13);
+        // a fine grid over a large station counts quickly and close to the area
+        let r = 1e5;
+        let n = disc_cells(r) as f64;
+        assert!((n / (std::f64::consts::PI * r * r) - 1.0).abs() < 1e-4);
+    }
+}
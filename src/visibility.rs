@@ -0,0 +1,201 @@
+//! Visibilities of an interferometer made of stations.
+//!
+//! Each station sees the sky through its jones matrix, the array factor of
+//! the station times the jones matrix of the element (rows: the feeds along x
+//! and y, columns: E_theta and E_phi, as `dipole::lp_ant_jones`).
+//! The sky is unpolarized; the phase of the visibilities follows the sign
+//! convention of `calc_array_beam1`, the voltage of a station at `r` seeing a
+//! source towards `s` is advanced by 2 pi r.s/lambda.
+
+use std::f64::consts::PI;
+
+use num::complex::Complex;
+use scorus::coordinates::Vec3d;
+
+/// station jones matrix in row-major order
+pub type Jones = [Complex<f64>; 4];
+
+/// a sky component seen from the site at one time
+#[derive(Clone, Copy, Debug)]
+pub struct SkyComponent {
+    /// east-north-up unit vector, above the horizon
+    pub dir: Vec3d<f64>,
+    /// stokes I in Jy
+    pub flux: f64,
+}
+
+/// coherency of two stations for an unpolarized source of flux `flux`,
+/// 1/2 flux J_i J_j^H, in the order xx, xy, yx, yy
+pub fn coherency(jones_i: &Jones, jones_j: &Jones, flux: f64) -> [Complex<f64>; 4] {
+    let mut result = [Complex::<f64>::default(); 4];
+    for p in 0..2 {
+        for q in 0..2 {
+            result[p * 2 + q] = (0..2)
+                .map(|k| jones_i[p * 2 + k] * jones_j[q * 2 + k].conj())
+                .sum::<Complex<f64>>()
+                * (flux / 2.0);
+        }
+    }
+    result
+}
+
+/// visibility of stations at `pos_i` and `pos_j` (in m), phased to `phase_center`;
+/// `jones_i` and `jones_j` are the station jones matrices towards the components
+pub fn calc_visibility(
+    comps: &[SkyComponent],
+    jones_i: &[Jones],
+    jones_j: &[Jones],
+    pos_i: &Vec3d<f64>,
+    pos_j: &Vec3d<f64>,
+    phase_center: &Vec3d<f64>,
+    lambda: f64,
+) -> [Complex<f64>; 4] {
+    let b = [pos_i.x - pos_j.x, pos_i.y - pos_j.y, pos_i.z - pos_j.z];
+    let mut result = [Complex::<f64>::default(); 4];
+    for (c, (ji, jj)) in comps.iter().zip(jones_i.iter().zip(jones_j.iter())) {
+        let dl = b[0] * (c.dir.x - phase_center.x)
+            + b[1] * (c.dir.y - phase_center.y)
+            + b[2] * (c.dir.z - phase_center.z);
+        let fringe = Complex::from_polar(1.0, 2.0 * PI * dl / lambda);
+        for (r, v) in result.iter_mut().zip(coherency(ji, jj, c.flux).iter()) {
+            *r += v * fringe;
+        }
+    }
+    result
+}
+
+/// u, v, w (in m) of the baseline `b` (east-north-up) for a station at
+/// latitude `lat`: w towards the phase center, v towards the celestial north pole
+pub fn calc_uvw(b: &Vec3d<f64>, phase_center: &Vec3d<f64>, lat: f64) -> (f64, f64, f64) {
+    let s = phase_center;
+    let pole = [0.0, lat.cos(), lat.sin()];
+    // pole x s is the direction of increasing ra, i.e., u
+    let mut u = [
+        pole[1] * s.z - pole[2] * s.y,
+        pole[2] * s.x - pole[0] * s.z,
+        pole[0] * s.y - pole[1] * s.x,
+    ];
+    let norm = (u[0] * u[0] + u[1] * u[1] + u[2] * u[2]).sqrt();
+    if norm > 0.0 {
+        u.iter_mut().for_each(|x| *x /= norm);
+    } else {
+        // phase center at the pole, u points to the east
+        u = [1.0, 0.0, 0.0];
+    }
+    let v = [
+        s.y * u[2] - s.z * u[1],
+        s.z * u[0] - s.x * u[2],
+        s.x * u[1] - s.y * u[0],
+    ];
+    (
+        b.x * u[0] + b.y * u[1] + b.z * u[2],
+        b.x * v[0] + b.y * v[1] + b.z * v[2],
+        b.x * s.x + b.y * s.y + b.z * s.z,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrary_array::calc_array_beam1;
+    use scorus::coordinates::SphCoord;
+
+    fn vec3d(x: f64, y: f64, z: f64) -> Vec3d<f64> {
+        Vec3d { x, y, z }
+    }
+
+    fn c(re: f64, im: f64) -> Complex<f64> {
+        Complex::new(re, im)
+    }
+
+    const IDENTITY: Jones = [
+        Complex::new(1.0, 0.0),
+        Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0),
+        Complex::new(1.0, 0.0),
+    ];
+
+    #[test]
+    fn source_at_the_phase_center_has_no_fringe() {
+        let ji = [c(1.0, 2.0), c(0.5, -1.0), c(-0.25, 0.0), c(2.0, 1.0)];
+        let jj = [c(0.0, 1.0), c(1.0, 1.0), c(3.0, 0.0), c(-1.0, 0.5)];
+        let flux = 4.0;
+        // 1/2 flux J_i J_j^H written out
+        let expected = [
+            (ji[0] * jj[0].conj() + ji[1] * jj[1].conj()) * 2.0,
+            (ji[0] * jj[2].conj() + ji[1] * jj[3].conj()) * 2.0,
+            (ji[2] * jj[0].conj() + ji[3] * jj[1].conj()) * 2.0,
+            (ji[2] * jj[2].conj() + ji[3] * jj[3].conj()) * 2.0,
+        ];
+        let s0 = Vec3d::from_sph_coord(SphCoord::new(0.3, 1.2));
+        let vis = calc_visibility(
+            &[SkyComponent { dir: s0, flux }],
+            &[ji],
+            &[jj],
+            &vec3d(12.0, -7.0, 0.5),
+            &vec3d(-3.0, 4.0, 0.0),
+            &s0,
+            2.0,
+        );
+        for ((v, k), e) in vis.iter().zip(coherency(&ji, &jj, flux)).zip(expected) {
+            assert!((v - e).norm() < 1e-12 && (k - e).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn fringe_follows_the_station_voltages() {
+        let lambda = 2.5;
+        let (pi, pj) = (vec3d(12.0, -7.0, 0.5), vec3d(-3.0, 4.0, 0.0));
+        let s0 = Vec3d::from_sph_coord(SphCoord::new(0.2, 0.4));
+        let s = Vec3d::from_sph_coord(SphCoord::new(0.35, 2.0));
+        let vis = calc_visibility(
+            &[SkyComponent { dir: s, flux: 2.0 }],
+            &[IDENTITY],
+            &[IDENTITY],
+            &pi,
+            &pj,
+            &s0,
+            lambda,
+        );
+        let dl = (pi.x - pj.x) * (s.x - s0.x)
+            + (pi.y - pj.y) * (s.y - s0.y)
+            + (pi.z - pj.z) * (s.z - s0.z);
+        assert!((vis[0] - Complex::from_polar(1.0, 2.0 * PI * dl / lambda)).norm() < 1e-12);
+        // the product of the voltages of single-antenna stations steered to s0
+        let voltage = |p: &Vec3d<f64>| {
+            let phi = 2.0 * PI * (p.x * s0.x + p.y * s0.y + p.z * s0.z) / lambda;
+            calc_array_beam1(&s, &[p.x], &[p.y], &[p.z], &[1.0], &[phi], lambda)
+        };
+        let expected = voltage(&pi) * voltage(&pj).conj();
+        for (k, v) in vis.iter().enumerate() {
+            let e = if k == 0 || k == 3 {
+                expected
+            } else {
+                Complex::default()
+            };
+            assert!((v - e).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn uvw_of_east_west_and_north_south_baselines() {
+        let lat = 40_f64.to_radians();
+        let (ew, ns) = (vec3d(10.0, 0.0, 0.0), vec3d(0.0, 10.0, 0.0));
+        let close = |(u, v, w): (f64, f64, f64), (u0, v0, w0): (f64, f64, f64)| {
+            (u - u0).abs() < 1e-12 && (v - v0).abs() < 1e-12 && (w - w0).abs() < 1e-12
+        };
+
+        // zenith: u east, v north
+        let zenith = vec3d(0.0, 0.0, 1.0);
+        assert!(close(calc_uvw(&ew, &zenith, lat), (10.0, 0.0, 0.0)));
+        assert!(close(calc_uvw(&ns, &zenith, lat), (0.0, 10.0, 0.0)));
+
+        // celestial pole: u falls back to east, v = w x u
+        let ncp = vec3d(0.0, lat.cos(), lat.sin());
+        assert!(close(calc_uvw(&ew, &ncp, lat), (10.0, 0.0, 0.0)));
+        assert!(close(
+            calc_uvw(&ns, &ncp, lat),
+            (0.0, 10.0 * lat.sin(), 10.0 * lat.cos())
+        ));
+    }
+}
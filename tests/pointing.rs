@@ -0,0 +1,59 @@
+use std::f64::consts::FRAC_PI_2;
+
+use jm21cma::{
+    arbitrary_array::{calc_array_beam1, calc_phase_from_pointing},
+    coordinates::AltAz,
+};
+use scorus::coordinates::Vec3d;
+
+fn assert_close(a: Vec3d<f64>, b: Vec3d<f64>) {
+    assert!(
+        (a.x - b.x).abs() < 1e-12 && (a.y - b.y).abs() < 1e-12 && (a.z - b.z).abs() < 1e-12,
+        "{:?} != {:?}",
+        a,
+        b
+    );
+}
+
+#[test]
+fn cardinal_directions() {
+    let v = |x, y, z| Vec3d { x, y, z };
+    assert_close(AltAz::new(0.0, 0.0).to_vec3d(), v(0.0, 1.0, 0.0));
+    assert_close(AltAz::new(0.0, FRAC_PI_2).to_vec3d(), v(1.0, 0.0, 0.0));
+    assert_close(AltAz::new(0.0, -FRAC_PI_2).to_vec3d(), v(-1.0, 0.0, 0.0));
+    assert_close(AltAz::new(FRAC_PI_2, 1.0).to_vec3d(), v(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn sph_coord_matches_vec3d() {
+    for &(zenith, az) in &[(20.0, 0.0), (45.0, 60.0), (70.0, 200.0), (30.0, -115.0)] {
+        let dir = AltAz::from_zenith_az(f64::to_radians(zenith), f64::to_radians(az));
+        assert_close(Vec3d::from_sph_coord(dir.to_sph_coord()), dir.to_vec3d());
+        assert_close(
+            AltAz::from_sph_coord(&dir.to_sph_coord()).to_vec3d(),
+            dir.to_vec3d(),
+        );
+        assert_close(
+            AltAz::from_vec3d(&dir.to_vec3d()).to_vec3d(),
+            dir.to_vec3d(),
+        );
+    }
+}
+
+#[test]
+fn array_beam_peaks_at_pointing() {
+    let x = [0.0, 3.0, -2.0, 5.0, 1.0];
+    let y = [0.0, 1.0, 4.0, -3.0, -2.5];
+    let z = [0.0; 5];
+    let w = [1.0; 5];
+    let lambda = 2.0;
+    let ptg = AltAz::from_zenith_az(40_f64.to_radians(), 30_f64.to_radians());
+    let phases = calc_phase_from_pointing(&x, &y, &z, &ptg, lambda);
+
+    let peak = calc_array_beam1(&ptg.to_vec3d(), &x, &y, &z, &w, &phases, lambda).norm();
+    assert!((peak - 5.0).abs() < 1e-9);
+
+    let mirrored = AltAz::from_zenith_az(40_f64.to_radians(), -30_f64.to_radians());
+    let off = calc_array_beam1(&mirrored.to_vec3d(), &x, &y, &z, &w, &phases, lambda).norm();
+    assert!(off < 4.0);
+}